@@ -0,0 +1,948 @@
+use crate::error::EngineError;
+use crate::history::{Command, Entry, Event};
+use crate::resolver::DefinitionResolver;
+use crate::state::{Position, State};
+use std::sync::Arc;
+use wfrs_model::json::JsonValue;
+use wfrs_model::{CallActivityDef, Flow, TaskDef, WorkflowDefinition};
+use wfrs_validator::{
+    Context, ExclusiveGateway, FunctionRegistry, InclusiveGateway, PROCESS_VARIABLES,
+};
+
+pub const DEFAULT_STEP_BUDGET: usize = 10_000;
+pub const MAX_CALL_DEPTH: usize = 32;
+
+fn task_name(definition: &WorkflowDefinition, task_id: i32) -> String {
+    definition
+        .task_ids
+        .get(task_id as usize)
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| task_id.to_string())
+}
+
+// counts the tokens a single command may consume across all child scopes, a flow is
+// charged to its target
+struct Budget<'a> {
+    remaining: usize,
+    gateway: Option<(&'a WorkflowDefinition, i32)>,
+}
+
+impl<'a> Budget<'a> {
+    fn new(steps: usize) -> Self {
+        Self {
+            remaining: steps,
+            gateway: None,
+        }
+    }
+
+    fn spend(
+        &mut self,
+        definition: &'a WorkflowDefinition,
+        task_id: i32,
+    ) -> Result<(), EngineError> {
+        if definition
+            .tasks
+            .get(task_id as usize)
+            .is_some_and(|task| task.is_gateway())
+        {
+            self.gateway = Some((definition, task_id));
+        }
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            return Ok(());
+        }
+        // every cycle in a diagram passes a gateway unless tasks loop back directly
+        Err(match self.gateway {
+            Some((definition, gateway)) => {
+                EngineError::InfiniteLoop(task_name(definition, gateway))
+            }
+            None => EngineError::BudgetExhausted(task_name(definition, task_id)),
+        })
+    }
+}
+
+// drives a single instance synchronously, every command works on a borrowed `State`
+#[derive(Clone)]
+pub struct Engine<'a> {
+    pub entity_id: String,
+    pub definition: &'a WorkflowDefinition,
+    pub resolver: Option<Arc<dyn DefinitionResolver<'a> + 'a>>,
+    pub functions: Arc<FunctionRegistry>,
+    pub env: Arc<JsonValue>,
+    pub scope: Vec<i32>,
+    pub step_budget: usize,
+}
+
+impl<'a> Engine<'a> {
+    pub fn new(definition: &'a WorkflowDefinition, entity_id: String) -> Self {
+        Self {
+            entity_id,
+            definition,
+            resolver: None,
+            functions: Arc::new(FunctionRegistry::default()),
+            env: Arc::new(JsonValue::map()),
+            scope: vec![],
+            step_budget: DEFAULT_STEP_BUDGET,
+        }
+    }
+
+    pub fn with_resolver(mut self, resolver: Arc<dyn DefinitionResolver<'a> + 'a>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn with_functions(mut self, functions: Arc<FunctionRegistry>) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    pub fn with_env(mut self, env: Arc<JsonValue>) -> Self {
+        self.env = env;
+        self
+    }
+
+    // pins `now()` to `clock`, calls with arguments still go to the registered function
+    pub fn with_clock(mut self, clock: JsonValue) -> Self {
+        let now = self.functions.get("now").cloned();
+        let mut functions = (*self.functions).clone();
+        functions.register("now", move |args: &[JsonValue]| match &now {
+            Some(now) if !args.is_empty() => now(args),
+            _ => Ok(clock.clone()),
+        });
+        self.functions = Arc::new(functions);
+        self
+    }
+
+    // evaluates a recorded command against the env and clock it first ran with
+    pub(crate) fn recorded(&self, entry: &Entry) -> Engine<'a> {
+        let engine = self.clone().with_env(Arc::new(entry.env.clone()));
+        match &entry.clock {
+            Some(clock) => engine.with_clock(clock.clone()),
+            None => engine,
+        }
+    }
+
+    pub fn with_step_budget(mut self, step_budget: usize) -> Self {
+        self.step_budget = step_budget;
+        self
+    }
+
+    pub fn start(&self) -> State {
+        State::new_in(self.definition.root_start_event(), &self.scope)
+    }
+
+    fn resolve(&self, called_element: &str) -> Option<&'a WorkflowDefinition> {
+        self.resolver.as_ref()?.resolve(called_element)
+    }
+
+    fn child(&self, definition: &'a WorkflowDefinition, task_id: i32) -> Engine<'a> {
+        let mut scope = self.scope.clone();
+        scope.push(task_id);
+        Engine {
+            entity_id: definition.format_id(&self.entity_id),
+            definition,
+            resolver: self.resolver.clone(),
+            functions: self.functions.clone(),
+            env: self.env.clone(),
+            scope,
+            step_budget: self.step_budget,
+        }
+    }
+
+    fn metadata(&self, state: &State) -> JsonValue {
+        let mut metadata = JsonValue::map();
+        if let Some(obj) = metadata.as_object_mut() {
+            obj.insert(
+                "entity_id".to_string(),
+                JsonValue::String(self.entity_id.clone()),
+            );
+            obj.insert(
+                "definition".to_string(),
+                JsonValue::String(self.definition.key()),
+            );
+            obj.insert("completed".to_string(), JsonValue::Bool(state.completed));
+            obj.insert(
+                "visited".to_string(),
+                JsonValue::Number((state.visited_tasks.len() as f64).into()),
+            );
+        }
+        metadata
+    }
+
+    fn context<'s>(&'s self, state: &'s State, metadata: &'s JsonValue) -> Context<'s> {
+        Context::new(&state.variables, &self.functions)
+            .with_env(&self.env)
+            .with_instance(metadata)
+    }
+
+    pub fn process_variables(&self, state: &State) -> JsonValue {
+        state
+            .variables
+            .get_path(PROCESS_VARIABLES)
+            .cloned()
+            .unwrap_or_else(JsonValue::map)
+    }
+
+    pub fn set_process_variables(
+        &self,
+        state: &mut State,
+        variables: JsonValue,
+    ) -> Result<(), String> {
+        self.execute(state, Command::SetProcessVariables { variables })
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn set_variables(
+        &self,
+        state: &mut State,
+        task_id: i32,
+        variables: JsonValue,
+    ) -> Result<(), String> {
+        self.set_variables_in(state, &[], task_id, variables)
+    }
+
+    pub fn set_variables_in(
+        &self,
+        state: &mut State,
+        scope: &[i32],
+        task_id: i32,
+        variables: JsonValue,
+    ) -> Result<(), String> {
+        let command = Command::SetVariables {
+            scope: scope.to_vec(),
+            task: task_id,
+            variables,
+        };
+        self.execute(state, command).map_err(|err| err.to_string())
+    }
+
+    pub fn complete(&self, state: &mut State, task_id: i32) -> Result<(), String> {
+        self.complete_in(state, &[], task_id)
+    }
+
+    pub fn complete_in(
+        &self,
+        state: &mut State,
+        scope: &[i32],
+        task_id: i32,
+    ) -> Result<(), String> {
+        let command = Command::Complete {
+            scope: scope.to_vec(),
+            task: task_id,
+        };
+        self.execute(state, command).map_err(|err| err.to_string())
+    }
+
+    pub fn run(&self, state: &mut State) -> Result<(), EngineError> {
+        self.execute(state, Command::Run)
+    }
+
+    // a failed command leaves `state` untouched, commands without events changed
+    // nothing and are not recorded
+    pub fn execute(&self, state: &mut State, command: Command) -> Result<(), EngineError> {
+        let history = std::mem::take(&mut state.history);
+        let before = state.clone();
+        state.history = history;
+        // `now()` is read once per command and recorded, so a replay can pin it
+        let clock = self.functions.get("now").and_then(|now| now(&[]).ok());
+        let engine = match &clock {
+            Some(clock) => self.clone().with_clock(clock.clone()),
+            None => self.clone(),
+        };
+        let result = engine.apply(state, &command);
+        let events = std::mem::take(&mut state.history.pending);
+        if let Err(err) = result {
+            let history = std::mem::take(&mut state.history);
+            *state = before;
+            state.history = history;
+            return Err(err);
+        }
+        if !events.is_empty() {
+            state.history.base.get_or_insert_with(|| Box::new(before));
+            state.history.entries.push(Entry {
+                command,
+                events,
+                env: self.env.as_ref().clone(),
+                clock,
+            });
+        }
+        Ok(())
+    }
+
+    // undo is recorded like any other command, so an undone step can be restored by
+    // undoing to a later entry; an undo whose state cannot be simulated is dropped
+    pub fn undo(&self, state: &mut State, step: usize) -> Result<(), EngineError> {
+        let before = state.clone();
+        self.execute(state, Command::Undo { to: step as u64 })?;
+        if let Err(err) = self.simulate(state) {
+            *state = before;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    // replays the first `to` entries on the base, the history itself is left intact
+    fn rewind(&self, state: &mut State, to: u64) -> Result<(), EngineError> {
+        let len = state.history.len();
+        let to = usize::try_from(to).unwrap_or(usize::MAX);
+        if to > len {
+            return Err(EngineError::Rejected(format!(
+                "unable to undo to step {to}, the history has {len} entries"
+            )));
+        }
+        if to == len {
+            return Ok(());
+        }
+        let history = std::mem::take(&mut state.history);
+        let mut replayed = match &history.base {
+            Some(base) => base.as_ref().clone(),
+            None => self.start(),
+        };
+        for entry in &history.entries[..to] {
+            let engine = self.recorded(entry);
+            if let Err(err) = engine.execute(&mut replayed, entry.command.clone()) {
+                state.history = history;
+                return Err(err);
+            }
+        }
+        replayed.remote_id = state.remote_id.take();
+        replayed.remote_version = state.remote_version.take();
+        replayed.history = history;
+        *state = replayed;
+        state.history.record(Event::Undone { to: to as u64 });
+        Ok(())
+    }
+
+    fn apply(&self, state: &mut State, command: &Command) -> Result<(), EngineError> {
+        let mut budget = Budget::new(self.step_budget);
+        match command {
+            Command::Run => self.drive(state, &mut budget),
+            Command::Complete { scope, task } => {
+                self.complete_task(state, scope, *task, &mut budget)
+            }
+            Command::NavigateTo { task } => {
+                if !self.is_usertask(*task) {
+                    return Err(EngineError::Rejected(format!(
+                        "task with id {task} is not a usertask"
+                    )));
+                }
+                state.set_usertask(*task, &self.scope);
+                state.history.record(Event::TaskActivated {
+                    scope: self.scope.clone(),
+                    task: *task,
+                });
+                Ok(())
+            }
+            Command::SetVariables {
+                scope,
+                task,
+                variables,
+            } => self.merge_variables(state, scope, *task, variables),
+            Command::Undo { to } => self.rewind(state, *to),
+            Command::SetProcessVariables { variables } => {
+                let JsonValue::Object(variables) = variables else {
+                    return Err(EngineError::Rejected(
+                        "process variables must be an object".to_string(),
+                    ));
+                };
+                if let Some(obj) = state.variables.as_object_mut() {
+                    let process = obj
+                        .entry(PROCESS_VARIABLES.to_string())
+                        .or_insert_with(JsonValue::map);
+                    if !process.is_object() {
+                        *process = JsonValue::map();
+                    }
+                    if let Some(process) = process.as_object_mut() {
+                        process.extend(variables.clone());
+                    }
+                }
+                state.history.record(Event::VariablesChanged {
+                    scope: self.scope.clone(),
+                    task: None,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn merge_variables(
+        &self,
+        state: &mut State,
+        scope: &[i32],
+        task_id: i32,
+        variables: &JsonValue,
+    ) -> Result<(), EngineError> {
+        if let Some((task, scope)) = scope.split_first() {
+            let (child, mut child_state) = self.take_child(state, *task)?;
+            let result = child.merge_variables(&mut child_state, scope, task_id, variables);
+            self.leave_child(state, *task, &child, child_state);
+            return result;
+        }
+        let JsonValue::Object(variables) = variables else {
+            return Err(EngineError::Rejected(
+                "variables must be an object".to_string(),
+            ));
+        };
+        if !state.is_waiting(task_id) {
+            return Err(EngineError::Rejected(format!(
+                "task with id {task_id} is not waiting"
+            )));
+        }
+        let key = self.definition.task_ids[task_id as usize].to_string();
+        if let Some(obj) = state.variables.as_object_mut() {
+            let current = obj.entry(key).or_insert_with(JsonValue::map);
+            if !current.is_object() {
+                *current = JsonValue::map();
+            }
+            if let Some(current) = current.as_object_mut() {
+                current.extend(variables.clone());
+            }
+        }
+        state.history.record(Event::VariablesChanged {
+            scope: self.scope.clone(),
+            task: Some(task_id),
+        });
+        Ok(())
+    }
+
+    fn complete_task(
+        &self,
+        state: &mut State,
+        scope: &[i32],
+        task_id: i32,
+        budget: &mut Budget<'a>,
+    ) -> Result<(), EngineError> {
+        if let Some((task, scope)) = scope.split_first() {
+            let (child, mut child_state) = self.take_child(state, *task)?;
+            let result = child.complete_task(&mut child_state, scope, task_id, budget);
+            if self.leave_child(state, *task, &child, child_state) {
+                state.take_token(Position::Child(*task));
+                if let Some(task) = self.definition.tasks.get(*task as usize) {
+                    self.visit_outgoing(state, task.outgoing());
+                    self.drive(state, budget)?;
+                }
+            }
+            return result;
+        }
+        if state.take_token(Position::Waiting(task_id)).is_none() {
+            return Err(EngineError::Rejected(format!(
+                "task with id {task_id} not found"
+            )));
+        }
+        match self
+            .definition
+            .tasks
+            .get(task_id as usize)
+            .map(|task| &task.def)
+        {
+            Some(TaskDef::UserTask(ev)) => {
+                state.history.record(Event::TaskCompleted {
+                    scope: self.scope.clone(),
+                    task: task_id,
+                });
+                self.visit_outgoing(state, &ev.outgoing);
+                self.drive(state, budget)
+            }
+            Some(_) => Err(EngineError::Rejected(format!(
+                "task with id {task_id} is not a usertask"
+            ))),
+            None => Err(EngineError::Rejected(format!(
+                "no pending usertask with id {task_id}"
+            ))),
+        }
+    }
+
+    fn visit_outgoing(&self, state: &mut State, outgoing: &[i32]) {
+        for outgoing in outgoing {
+            state.spawn(Position::Flow(*outgoing), &self.scope);
+        }
+    }
+
+    fn drive(&self, state: &mut State, budget: &mut Budget<'a>) -> Result<(), EngineError> {
+        loop {
+            while let Some(token) = state.next_token() {
+                match token.position {
+                    Position::Task(task) => {
+                        budget.spend(self.definition, task)?;
+                        self.run_task(state, task, budget)?;
+                    }
+                    Position::Flow(flow) => {
+                        if let Some(current_flow) = self.definition.flows.get(flow as usize) {
+                            budget.spend(self.definition, current_flow.target_ref)?;
+                            self.follow_flow(state, current_flow);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let Some(gateway) = self.ready_inclusive_join(state) else {
+                return Ok(());
+            };
+            self.try_fire_join(state, gateway);
+        }
+    }
+
+    fn run_task(
+        &self,
+        state: &mut State,
+        task_id: i32,
+        budget: &mut Budget<'a>,
+    ) -> Result<(), EngineError> {
+        let Some(current_task) = self.definition.tasks.get(task_id as usize) else {
+            return Ok(());
+        };
+        match &current_task.def {
+            TaskDef::StartEvent(ev) => {
+                self.visit_outgoing(state, &ev.outgoing);
+            }
+            TaskDef::UserTask(_) => {
+                state.spawn(Position::Waiting(current_task.id), &self.scope);
+                state.push_visited_task(current_task.id);
+                state.history.record(Event::TaskActivated {
+                    scope: self.scope.clone(),
+                    task: current_task.id,
+                });
+            }
+            TaskDef::ExclusiveGateway(ev) => {
+                let metadata = self.metadata(state);
+                let ctx = self.context(state, &metadata);
+                let out = ExclusiveGateway(ev).evaluate(self.definition, &ctx);
+                self.take_gateway(state, current_task.id, &out);
+            }
+            // joins only hand out a task token once `try_fire_join` let them through
+            TaskDef::ParallelGateway(ev) => {
+                state.push_visited_task(current_task.id);
+                self.take_gateway(state, current_task.id, &ev.outgoing);
+            }
+            TaskDef::InclusiveGateway(ev) => {
+                let metadata = self.metadata(state);
+                let ctx = self.context(state, &metadata);
+                let out = InclusiveGateway(ev).evaluate(self.definition, &ctx);
+                state.push_visited_task(current_task.id);
+                self.take_gateway(state, current_task.id, &out);
+            }
+            TaskDef::SubProcess(ev) => {
+                state.push_visited_task(current_task.id);
+                if self.enter_sub_process(state, current_task.id, budget)? {
+                    self.visit_outgoing(state, &ev.outgoing);
+                } else {
+                    state.spawn(Position::Child(current_task.id), &self.scope);
+                }
+            }
+            TaskDef::CallActivity(ev) => {
+                state.push_visited_task(current_task.id);
+                if self.enter_call_activity(state, current_task.id, ev, budget)? {
+                    self.visit_outgoing(state, &ev.outgoing);
+                } else {
+                    state.spawn(Position::Child(current_task.id), &self.scope);
+                }
+            }
+            TaskDef::PassThrough(ev) => {
+                state.push_visited_task(current_task.id);
+                self.visit_outgoing(state, &ev.outgoing);
+            }
+            TaskDef::EndEvent(_) => {
+                if !state.completed {
+                    state.try_set_completed();
+                    if state.completed {
+                        state.history.record(Event::Completed {
+                            scope: self.scope.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn take_gateway(&self, state: &mut State, gateway: i32, outgoing: &[i32]) {
+        state.history.record(Event::GatewayEvaluated {
+            scope: self.scope.clone(),
+            gateway,
+            outgoing: outgoing.to_vec(),
+        });
+        self.visit_outgoing(state, outgoing);
+    }
+
+    fn follow_flow(&self, state: &mut State, current_flow: &Flow) {
+        state.visited_flows.push(current_flow.id);
+        state.history.record(Event::FlowTaken {
+            scope: self.scope.clone(),
+            flow: current_flow.id,
+        });
+        state.push_visited_task(current_flow.source_ref);
+        if self.is_join(current_flow.target_ref) {
+            state.spawn(Position::Joined(current_flow.id), &self.scope);
+            self.try_fire_join(state, current_flow.target_ref);
+        } else {
+            state.spawn(Position::Task(current_flow.target_ref), &self.scope);
+        }
+    }
+
+    fn try_fire_join(&self, state: &mut State, gateway: i32) {
+        let fire = match self.definition.tasks.get(gateway as usize).map(|t| &t.def) {
+            Some(TaskDef::ParallelGateway(ev)) => state.try_join(&ev.incoming),
+            Some(TaskDef::InclusiveGateway(ev)) => {
+                self.is_inclusive_join_ready(state, gateway, &ev.incoming)
+                    && state.try_join_arrived(&ev.incoming)
+            }
+            _ => false,
+        };
+        if fire {
+            state.spawn(Position::Task(gateway), &self.scope);
+        }
+    }
+
+    // nested scopes recurse on the stack, a process calling itself would otherwise only
+    // stop once the step budget is used up
+    fn check_depth(&self, task_id: i32) -> Result<(), EngineError> {
+        if self.scope.len() >= MAX_CALL_DEPTH {
+            return Err(EngineError::CallDepthExceeded(task_name(
+                self.definition,
+                task_id,
+            )));
+        }
+        Ok(())
+    }
+
+    fn enter_sub_process(
+        &self,
+        state: &mut State,
+        task_id: i32,
+        budget: &mut Budget<'a>,
+    ) -> Result<bool, EngineError> {
+        if let Some(definition) = self.definition.sub_process(task_id) {
+            self.check_depth(task_id)?;
+            let child = self.child(definition, task_id);
+            let mut child_state = child.start();
+            child_state.variables = state.variables.clone();
+            child.drive(&mut child_state, budget)?;
+            Ok(self.leave_child(state, task_id, &child, child_state))
+        } else {
+            Ok(true)
+        }
+    }
+
+    fn enter_call_activity(
+        &self,
+        state: &mut State,
+        task_id: i32,
+        call: &CallActivityDef,
+        budget: &mut Budget<'a>,
+    ) -> Result<bool, EngineError> {
+        let Some(definition) = self.resolve(&call.called_element) else {
+            return Err(EngineError::UnresolvedCallActivity {
+                called_element: call.called_element.to_string(),
+            });
+        };
+        self.check_depth(task_id)?;
+        let child = self.child(definition, task_id);
+        let mut child_state = child.start();
+        for mapping in call.inputs.iter() {
+            if let Some(value) = state.variables.get_path(&mapping.source) {
+                child_state
+                    .variables
+                    .set_path(&mapping.target, value.clone());
+            }
+        }
+        child.drive(&mut child_state, budget)?;
+        Ok(self.leave_child(state, task_id, &child, child_state))
+    }
+
+    fn take_child(
+        &self,
+        state: &mut State,
+        task_id: i32,
+    ) -> Result<(Engine<'a>, State), EngineError> {
+        let called = state
+            .children
+            .iter()
+            .find(|child| child.task == task_id)
+            .map(|child| child.definition.clone())
+            .ok_or_else(|| {
+                EngineError::Rejected(format!(
+                    "no active child process for task with id {task_id}"
+                ))
+            })?;
+        let definition = match called {
+            Some(key) => self.resolve(&key),
+            None => self.definition.sub_process(task_id),
+        }
+        .ok_or_else(|| {
+            EngineError::Rejected(format!(
+                "unable to resolve child process for task with id {task_id}"
+            ))
+        })?;
+        let child = self.child(definition, task_id);
+        let child_state = match state.take_child(task_id) {
+            Some(child_state) => child_state.state,
+            None => child.start(),
+        };
+        Ok((child, child_state))
+    }
+
+    fn leave_child(
+        &self,
+        state: &mut State,
+        task_id: i32,
+        child: &Engine<'a>,
+        mut child_state: State,
+    ) -> bool {
+        state
+            .history
+            .pending
+            .append(&mut child_state.history.pending);
+        let call = match self.definition.tasks.get(task_id as usize).map(|t| &t.def) {
+            Some(TaskDef::CallActivity(call)) => Some(call),
+            _ => None,
+        };
+        if !child_state.completed {
+            let definition = call.map(|_| child.definition.key());
+            state.push_child(task_id, definition, child_state);
+            return false;
+        }
+        let mut scoped = JsonValue::map();
+        if let Some((variables, scoped)) = child_state
+            .variables
+            .as_object()
+            .zip(scoped.as_object_mut())
+        {
+            for key in child.definition.task_ids.iter() {
+                if let Some(value) = variables.get(key.as_ref()) {
+                    scoped.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+        let key = self.definition.task_ids[task_id as usize].to_string();
+        if let Some(variables) = state.variables.as_object_mut() {
+            variables.insert(key, scoped);
+        }
+        if call.is_none() {
+            if let Some(variables) = child_state.variables.get_path(PROCESS_VARIABLES) {
+                state
+                    .variables
+                    .set_path(PROCESS_VARIABLES, variables.clone());
+            }
+        }
+        if let Some(call) = call {
+            for mapping in call.outputs.iter() {
+                if let Some(value) = child_state.variables.get_path(&mapping.source) {
+                    state.variables.set_path(&mapping.target, value.clone());
+                }
+            }
+        }
+        true
+    }
+
+    fn ready_inclusive_join(&self, state: &State) -> Option<i32> {
+        self.definition
+            .tasks
+            .iter()
+            .find_map(|task| match &task.def {
+                TaskDef::InclusiveGateway(ev)
+                    if self.is_inclusive_join_ready(state, task.id, &ev.incoming) =>
+                {
+                    Some(task.id)
+                }
+                _ => None,
+            })
+    }
+
+    fn is_inclusive_join_ready(&self, state: &State, gateway: i32, incoming: &[i32]) -> bool {
+        let joined = state.joined_flows();
+        if !incoming.iter().any(|flow| joined.contains(flow)) {
+            return false;
+        }
+        let missing: Vec<i32> = incoming
+            .iter()
+            .copied()
+            .filter(|flow| !joined.contains(flow))
+            .collect();
+        if missing.is_empty() {
+            return true;
+        }
+        let current_flows = state.current_flows();
+        if current_flows.iter().any(|flow| missing.contains(flow)) {
+            return false;
+        }
+        let tokens = state
+            .pending_tasks()
+            .into_iter()
+            .chain(state.current_tasks())
+            .chain(state.children.iter().map(|child| child.task))
+            .chain(
+                current_flows
+                    .iter()
+                    .chain(joined.iter().filter(|flow| !incoming.contains(flow)))
+                    .filter_map(|flow| self.definition.flows.get(*flow as usize))
+                    .map(|flow| flow.target_ref),
+            )
+            .filter(|task| *task != gateway)
+            .collect::<Vec<i32>>();
+        !self.can_reach(tokens, &missing, gateway)
+    }
+
+    fn can_reach(&self, mut tasks: Vec<i32>, flows: &[i32], stop: i32) -> bool {
+        let mut seen = Vec::new();
+        while let Some(task_id) = tasks.pop() {
+            if seen.contains(&task_id) {
+                continue;
+            }
+            seen.push(task_id);
+            if let Some(task) = self.definition.tasks.get(task_id as usize) {
+                for outgoing in task.outgoing() {
+                    if flows.contains(outgoing) {
+                        return true;
+                    }
+                    if let Some(flow) = self.definition.flows.get(*outgoing as usize) {
+                        if flow.target_ref != stop {
+                            tasks.push(flow.target_ref);
+                        }
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    pub fn set_default_active_task(&self, state: &mut State) {
+        state.active = state.pending_tasks().first().copied().unwrap_or(-1);
+    }
+
+    fn is_usertask(&self, task_id: i32) -> bool {
+        self.definition
+            .tasks
+            .get(task_id as usize)
+            .map(|ev| ev.is_user_task())
+            .unwrap_or(false)
+    }
+
+    fn is_join(&self, task_id: i32) -> bool {
+        self.definition
+            .tasks
+            .get(task_id as usize)
+            .map(|ev| ev.is_parallel_gateway() || ev.is_inclusive_gateway())
+            .unwrap_or(false)
+    }
+
+    fn sim_run(&self, state: &mut State) -> Result<(), EngineError> {
+        let mut budget = Budget::new(self.step_budget);
+        loop {
+            let future_task = state
+                .maybe_future_tasks
+                .pop()
+                .and_then(|task| self.definition.tasks.get(task as usize));
+            if let Some(future_task) = future_task {
+                budget.spend(self.definition, future_task.id)?;
+                match &future_task.def {
+                    TaskDef::StartEvent(ev) => {
+                        state.maybe_future_flows.extend_from_slice(&ev.outgoing);
+                    }
+                    TaskDef::UserTask(ev) => {
+                        state.maybe_future_flows.extend_from_slice(&ev.outgoing);
+                    }
+                    TaskDef::ExclusiveGateway(ev) => {
+                        let metadata = self.metadata(state);
+                        let ctx = self.context(state, &metadata);
+                        let out = ExclusiveGateway(ev).evaluate(self.definition, &ctx);
+                        state.maybe_future_flows.extend_from_slice(&out);
+                    }
+                    TaskDef::ParallelGateway(ev) => {
+                        if state.try_maybe_join(&ev.incoming) {
+                            state.maybe_future_flows.extend_from_slice(&ev.outgoing);
+                        }
+                    }
+                    TaskDef::InclusiveGateway(ev) => {
+                        if state.is_first_maybe_join(&ev.incoming) {
+                            let metadata = self.metadata(state);
+                            let ctx = self.context(state, &metadata);
+                            let out = InclusiveGateway(ev).evaluate(self.definition, &ctx);
+                            state.maybe_future_flows.extend_from_slice(&out);
+                        }
+                    }
+                    TaskDef::SubProcess(ev) => {
+                        state.maybe_future_flows.extend_from_slice(&ev.outgoing);
+                    }
+                    TaskDef::CallActivity(ev) => {
+                        state.maybe_future_flows.extend_from_slice(&ev.outgoing);
+                    }
+                    TaskDef::PassThrough(ev) => {
+                        state.maybe_future_flows.extend_from_slice(&ev.outgoing);
+                    }
+                    TaskDef::EndEvent(_) => {}
+                }
+                continue;
+            }
+            let future_flow = state
+                .maybe_future_flows
+                .pop()
+                .and_then(|flow| self.definition.flows.get(flow as usize));
+            let Some(future_flow) = future_flow else {
+                return Ok(());
+            };
+            budget.spend(self.definition, future_flow.target_ref)?;
+            if self.is_join(future_flow.target_ref) {
+                state.push_maybe_joined_flow(future_flow.id);
+            }
+            state.maybe_future_tasks.push(future_flow.target_ref);
+            if self.is_usertask(future_flow.source_ref) {
+                state.maybe_visited_tasks.push(future_flow.source_ref);
+            }
+        }
+    }
+
+    pub fn simulate(&self, state: &mut State) -> Result<(), EngineError> {
+        state.clear_future(self.definition.root_start_event());
+        self.sim_run(state)
+    }
+
+    pub fn navigate_to(&self, state: &mut State, task_id: i32) -> Result<(), EngineError> {
+        let Some(task) = self.definition.tasks.get(task_id as usize) else {
+            return Ok(());
+        };
+        let visited =
+            state.visited_tasks.contains(&task_id) && state.maybe_visited_tasks.contains(&task_id);
+        let target = if task.is_user_task() && visited {
+            Some(task_id)
+        } else {
+            self.get_previous_user_task(state, task_id)
+                .filter(|task_id| state.visited_tasks.contains(task_id))
+        };
+        if let Some(task) = target {
+            self.execute(state, Command::NavigateTo { task })?;
+            self.simulate(state)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_previous_user_task(&self, state: &State, task_id: i32) -> Option<i32> {
+        let mut task_id = task_id;
+        let mut result = None;
+        loop {
+            let pos = state
+                .visited_tasks
+                .iter()
+                .position(|t| t == &task_id)
+                .and_then(|pos| {
+                    if pos > 0 {
+                        state.visited_tasks.get(pos - 1)
+                    } else {
+                        None
+                    }
+                });
+            if let Some(pos) = pos {
+                if let Some(task) = self.definition.tasks.get(*pos as usize) {
+                    if task.is_user_task() {
+                        result = Some(*pos);
+                        break;
+                    } else {
+                        task_id = *pos;
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,24 @@
+use rkyv::de::deserializers::SharedDeserializeMapError;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum EngineError {
+    #[error("infinite loop detected at gateway {0}")]
+    InfiniteLoop(String),
+    #[error("step budget exhausted at task {0}")]
+    BudgetExhausted(String),
+    #[error("unable to resolve called element '{called_element}'")]
+    UnresolvedCallActivity { called_element: String },
+    #[error("call depth exceeded at task {0}")]
+    CallDepthExceeded(String),
+    #[error("{0}")]
+    Rejected(String),
+}
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("invalid state archive: {0}")]
+    Invalid(String),
+    #[error("unable to deserialize state: {0}")]
+    Deserialize(#[from] SharedDeserializeMapError),
+}
@@ -0,0 +1,145 @@
+use rkyv::{Archive, Deserialize, Serialize};
+use wfrs_model::json::JsonValue;
+
+use crate::state::State;
+
+#[derive(Archive, Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    check_bytes
+)]
+#[archive_attr(
+    check_bytes(
+        bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: rkyv::bytecheck::Error"
+    ),
+    derive(Debug)
+)]
+pub enum Command {
+    Run,
+    Complete {
+        scope: Vec<i32>,
+        task: i32,
+    },
+    // the user task `navigate_to` resolved to, so replaying does not depend on the simulation
+    NavigateTo {
+        task: i32,
+    },
+    SetVariables {
+        scope: Vec<i32>,
+        task: i32,
+        #[omit_bounds]
+        #[archive_attr(omit_bounds)]
+        variables: JsonValue,
+    },
+    SetProcessVariables {
+        #[omit_bounds]
+        #[archive_attr(omit_bounds)]
+        variables: JsonValue,
+    },
+    // rebuilds the state after the first `to` entries, the undone entries are kept
+    Undo {
+        to: u64,
+    },
+}
+
+#[derive(Archive, Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub enum Event {
+    TaskActivated {
+        scope: Vec<i32>,
+        task: i32,
+    },
+    TaskCompleted {
+        scope: Vec<i32>,
+        task: i32,
+    },
+    FlowTaken {
+        scope: Vec<i32>,
+        flow: i32,
+    },
+    GatewayEvaluated {
+        scope: Vec<i32>,
+        gateway: i32,
+        outgoing: Vec<i32>,
+    },
+    VariablesChanged {
+        scope: Vec<i32>,
+        task: Option<i32>,
+    },
+    Completed {
+        scope: Vec<i32>,
+    },
+    Undone {
+        to: u64,
+    },
+}
+
+#[derive(Archive, Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    check_bytes
+)]
+#[archive_attr(
+    check_bytes(
+        bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: rkyv::bytecheck::Error"
+    ),
+    derive(Debug)
+)]
+pub struct Entry {
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub command: Command,
+    pub events: Vec<Event>,
+    // what `$env` and `now()` were when the command ran, replays evaluate against them
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub env: JsonValue,
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub clock: Option<JsonValue>,
+}
+
+// `base` is the state before the first recorded command, replaying `entries` on it
+// reproduces the current state
+#[derive(Archive, Debug, Deserialize, Serialize, Clone, Default)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    check_bytes
+)]
+#[archive_attr(check_bytes(
+    bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: rkyv::bytecheck::Error"
+))]
+pub struct History {
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub base: Option<Box<State>>,
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub entries: Vec<Entry>,
+    // events of the command that is being executed, children hand theirs to the parent
+    pub pending: Vec<Event>,
+}
+
+impl History {
+    pub fn record(&mut self, event: Event) {
+        self.pending.push(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
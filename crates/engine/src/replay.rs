@@ -0,0 +1,88 @@
+use thiserror::Error;
+
+use crate::engine::Engine;
+use crate::error::EngineError;
+use crate::history::{Command, Event, History};
+use crate::state::State;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum DivergenceKind {
+    #[error("{0}")]
+    Failed(EngineError),
+    #[error("expected events {expected:?} but got {actual:?}")]
+    Events {
+        expected: Vec<Event>,
+        actual: Vec<Event>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("history diverges at step {step} ({command:?}): {kind}")]
+pub struct Divergence {
+    pub step: usize,
+    pub command: Box<Command>,
+    pub kind: DivergenceKind,
+}
+
+impl<'a> Engine<'a> {
+    // rebuilds the state a history describes and checks every command still produces
+    // the events that were recorded for it, under the env and clock it first ran with
+    pub fn replay(&self, history: &History) -> Result<State, Divergence> {
+        let mut state = match &history.base {
+            Some(base) => base.as_ref().clone(),
+            None => self.start(),
+        };
+        for (step, entry) in history.entries.iter().enumerate() {
+            self.recorded(entry).replay_step(
+                &mut state,
+                step,
+                &entry.command,
+                Some(&entry.events),
+            )?;
+        }
+        Ok(state)
+    }
+
+    // same as `replay` for a bare list of commands starting at the start event,
+    // without recorded events only failing commands count as divergence
+    pub fn replay_commands(&self, commands: &[Command]) -> Result<State, Divergence> {
+        let mut state = self.start();
+        for (step, command) in commands.iter().enumerate() {
+            self.replay_step(&mut state, step, command, None)?;
+        }
+        Ok(state)
+    }
+
+    fn replay_step(
+        &self,
+        state: &mut State,
+        step: usize,
+        command: &Command,
+        expected: Option<&[Event]>,
+    ) -> Result<(), Divergence> {
+        let divergence = |kind| Divergence {
+            step,
+            command: Box::new(command.clone()),
+            kind,
+        };
+        let recorded = state.history.len();
+        self.execute(state, command.clone())
+            .map_err(|err| divergence(DivergenceKind::Failed(err)))?;
+        let Some(expected) = expected else {
+            return Ok(());
+        };
+        let actual = state
+            .history
+            .entries
+            .get(recorded)
+            .map(|entry| entry.events.as_slice())
+            .unwrap_or_default();
+        if actual != expected {
+            return Err(divergence(DivergenceKind::Events {
+                expected: expected.to_vec(),
+                actual: actual.to_vec(),
+            }));
+        }
+        Ok(())
+    }
+}
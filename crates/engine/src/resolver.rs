@@ -0,0 +1,5 @@
+use wfrs_model::WorkflowDefinition;
+
+pub trait DefinitionResolver<'a>: Send + Sync {
+    fn resolve(&self, called_element: &str) -> Option<&'a WorkflowDefinition>;
+}
@@ -1,192 +1,413 @@
 use futures_locks::RwLock;
 use futures_locks::RwLockReadGuard;
 use futures_locks::RwLockWriteGuard;
-use rkyv::{Archive, Deserialize, Serialize};
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::ser::Serializer;
+use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
 use std::sync::Arc;
 
-#[derive(Archive, Debug, Deserialize, Serialize)]
-#[archive(bound(
-    serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
-    deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+use crate::error::StateError;
+use crate::history::History;
+
+mod legacy;
+
+// appended to every archive written by `serialize`, archives without a footer use the
+// layout from before tokens and are migrated by `deserialize`
+const STATE_FORMAT: &[u8; 8] = b"wfrs:st3";
+
+#[derive(Archive, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub enum Position {
+    Task(i32),
+    Flow(i32),
+    Waiting(i32),
+    Joined(i32),
+    Child(i32),
+}
+
+#[derive(Archive, Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Token {
+    pub id: u64,
+    pub position: Position,
+    pub scope: Vec<i32>,
+    pub created: u64,
+}
+
+#[derive(Archive, Debug, Deserialize, Serialize, Clone)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    check_bytes
+)]
+#[archive_attr(check_bytes(
+    bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: rkyv::bytecheck::Error"
 ))]
 pub struct State {
     pub active: i32,
-    pub current_tasks: Vec<i32>,
-    pub current_flows: Vec<i32>,
+    pub tokens: Vec<Token>,
+    pub next_token: u64,
+    pub step: u64,
     pub visited_tasks: Vec<i32>,
     pub visited_flows: Vec<i32>,
-    pub pending_tasks: Vec<i32>,
     pub maybe_future_tasks: Vec<i32>,
     pub maybe_future_flows: Vec<i32>,
     pub maybe_visited_tasks: Vec<i32>,
+    pub maybe_joined_flows: Vec<i32>,
     pub variables: wfrs_model::json::JsonValue,
     pub completed: bool,
     pub remote_id: Option<String>,
     pub remote_version: Option<i64>,
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub children: Vec<ChildState>,
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub history: History,
 }
 
-pub struct LockedState {
-    pub inner: State,
-}
-
-#[derive(Clone)]
-pub struct WorkflowState {
-    inner: Arc<RwLock<LockedState>>,
+#[derive(Archive, Debug, Deserialize, Serialize, Clone)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    check_bytes
+)]
+#[archive_attr(check_bytes(
+    bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: rkyv::bytecheck::Error"
+))]
+pub struct ChildState {
+    pub task: i32,
+    pub definition: Option<String>,
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub state: State,
 }
 
-impl WorkflowState {
+impl State {
     pub fn new(start_event: i32) -> Self {
-        Self {
-            inner: Arc::new(RwLock::new(LockedState {
-                inner: State {
-                    active: -1,
-                    current_tasks: vec![start_event],
-                    current_flows: vec![],
-                    visited_tasks: vec![],
-                    visited_flows: vec![],
-                    pending_tasks: vec![],
-                    maybe_future_tasks: vec![],
-                    maybe_future_flows: vec![],
-                    maybe_visited_tasks: vec![],
-                    variables: wfrs_model::json::JsonValue::map(),
-                    completed: false,
-                    remote_id: None,
-                    remote_version: None,
-                },
-            })),
-        }
+        Self::new_in(start_event, &[])
     }
 
-    pub fn from_state(state: State) -> Self {
-        Self {
-            inner: Arc::new(RwLock::new(LockedState { inner: state })),
-        }
+    pub fn new_in(start_event: i32, scope: &[i32]) -> Self {
+        let mut state = Self {
+            active: -1,
+            tokens: vec![],
+            next_token: 0,
+            step: 0,
+            visited_tasks: vec![],
+            visited_flows: vec![],
+            maybe_future_tasks: vec![],
+            maybe_future_flows: vec![],
+            maybe_visited_tasks: vec![],
+            maybe_joined_flows: vec![],
+            variables: wfrs_model::json::JsonValue::map(),
+            completed: false,
+            remote_id: None,
+            remote_version: None,
+            children: vec![],
+            history: History::default(),
+        };
+        state.spawn(Position::Task(start_event), scope);
+        state
     }
 
-    pub async fn replace(&self, state: State) {
-        self.inner.write().await.inner = state;
+    pub fn spawn(&mut self, position: Position, scope: &[i32]) -> u64 {
+        let id = self.next_token;
+        self.next_token += 1;
+        self.tokens.push(Token {
+            id,
+            position,
+            scope: scope.to_vec(),
+            created: self.step,
+        });
+        id
     }
 
-    pub async fn set_remote_id(&self, remote_id: String, remote_version: i64) {
-        let mut state = self.inner.write().await;
-        state.inner.remote_id = Some(remote_id);
-        state.inner.remote_version = Some(remote_version);
+    // tasks run before flows are followed and the newest token goes first,
+    // which keeps the depth first order of the former stacks
+    pub fn next_token(&mut self) -> Option<Token> {
+        let index = self
+            .tokens
+            .iter()
+            .rposition(|token| matches!(token.position, Position::Task(_)))
+            .or_else(|| {
+                self.tokens
+                    .iter()
+                    .rposition(|token| matches!(token.position, Position::Flow(_)))
+            })?;
+        self.step += 1;
+        Some(self.tokens.remove(index))
     }
 
-    pub async fn get_remote_id(&self) -> Option<String> {
-        self.inner.read().await.inner.remote_id.clone()
+    fn positions(&self, select: impl Fn(Position) -> Option<i32>) -> Vec<i32> {
+        self.tokens
+            .iter()
+            .filter_map(|token| select(token.position))
+            .collect()
     }
 
-    pub async fn has_visited(&self, user_task: i32) -> bool {
-        let state = self.inner.read().await;
-        state.inner.visited_tasks.contains(&user_task)
+    pub fn current_tasks(&self) -> Vec<i32> {
+        self.positions(|position| match position {
+            Position::Task(task) => Some(task),
+            _ => None,
+        })
     }
 
-    pub async fn has_maybe_visited(&self, user_task: i32) -> bool {
-        let state = self.inner.read().await;
-        state.inner.maybe_visited_tasks.contains(&user_task)
+    pub fn current_flows(&self) -> Vec<i32> {
+        self.positions(|position| match position {
+            Position::Flow(flow) => Some(flow),
+            _ => None,
+        })
     }
 
-    pub async fn set_usertask(&self, user_task: i32) {
-        let mut state = self.inner.write().await;
-        state.inner.current_tasks.clear();
-        state.inner.current_flows.clear();
-        state.inner.pending_tasks.clear();
-        state.inner.pending_tasks.push(user_task);
-        state.inner.active = user_task;
+    pub fn pending_tasks(&self) -> Vec<i32> {
+        self.positions(|position| match position {
+            Position::Waiting(task) => Some(task),
+            _ => None,
+        })
     }
 
-    pub async fn clear_future(&self, start_event: i32) {
-        let mut state = self.inner.write().await;
-        state.inner.maybe_future_tasks.clear();
-        state.inner.maybe_future_flows.clear();
-        state.inner.maybe_visited_tasks.clear();
-        state.inner.maybe_future_tasks.push(start_event);
+    pub fn joined_flows(&self) -> Vec<i32> {
+        self.positions(|position| match position {
+            Position::Joined(flow) => Some(flow),
+            _ => None,
+        })
     }
 
-    pub async fn set_active(&self, active: i32) {
-        self.inner.write().await.inner.active = active;
+    pub fn is_waiting(&self, task: i32) -> bool {
+        self.tokens
+            .iter()
+            .any(|token| token.position == Position::Waiting(task))
     }
 
-    pub async fn get_active(&self) -> i32 {
-        self.inner.read().await.inner.active
+    pub fn take_token(&mut self, position: Position) -> Option<Token> {
+        let index = self
+            .tokens
+            .iter()
+            .position(|token| token.position == position)?;
+        Some(self.tokens.remove(index))
     }
 
-    pub async fn set_completed(&self) {
-        self.inner.write().await.inner.completed = true;
+    pub fn set_usertask(&mut self, user_task: i32, scope: &[i32]) {
+        self.tokens.clear();
+        self.children.clear();
+        self.spawn(Position::Waiting(user_task), scope);
+        self.active = user_task;
     }
 
-    pub async fn pop_current_task(&self) -> Option<i32> {
-        self.inner.write().await.inner.current_tasks.pop()
+    pub fn clear_future(&mut self, start_event: i32) {
+        self.maybe_future_tasks.clear();
+        self.maybe_future_flows.clear();
+        self.maybe_visited_tasks.clear();
+        self.maybe_joined_flows.clear();
+        self.maybe_future_tasks.push(start_event);
     }
 
-    pub async fn push_current_task(&self, task: i32) {
-        self.inner.write().await.inner.current_tasks.push(task);
+    // tokens parked at a join do not keep the instance alive on their own
+    pub fn try_set_completed(&mut self) {
+        if self
+            .tokens
+            .iter()
+            .all(|token| matches!(token.position, Position::Joined(_)))
+            && self.children.is_empty()
+        {
+            self.completed = true;
+        }
     }
 
-    pub async fn pop_visited_task(&self) -> Option<i32> {
-        self.inner.write().await.inner.visited_tasks.pop()
+    pub fn push_visited_task(&mut self, task: i32) {
+        if !self.visited_tasks.contains(&task) {
+            self.visited_tasks.push(task);
+        }
     }
 
-    pub async fn push_visited_task(&self, task: i32) {
-        let mut state = self.inner.write().await;
-        let exist = state.inner.visited_tasks.contains(&task);
-        if !exist {
-            state.inner.visited_tasks.push(task);
+    // consumes one token per incoming flow once every flow has delivered one
+    pub fn try_join(&mut self, incoming: &[i32]) -> bool {
+        let joined = self.joined_flows();
+        if !incoming.iter().all(|flow| joined.contains(flow)) {
+            return false;
+        }
+        for flow in incoming {
+            self.take_token(Position::Joined(*flow));
+        }
+        true
+    }
+
+    // consumes every token that already arrived on one of the incoming flows
+    pub fn try_join_arrived(&mut self, incoming: &[i32]) -> bool {
+        let arrived = |token: &Token| matches!(token.position, Position::Joined(flow) if incoming.contains(&flow));
+        if !self.tokens.iter().any(arrived) {
+            return false;
+        }
+        self.tokens.retain(|token| !arrived(token));
+        true
+    }
+
+    pub fn push_maybe_joined_flow(&mut self, flow: i32) {
+        if !self.maybe_joined_flows.contains(&flow) {
+            self.maybe_joined_flows.push(flow);
+        }
+    }
+
+    pub fn try_maybe_join(&mut self, incoming: &[i32]) -> bool {
+        if incoming
+            .iter()
+            .all(|flow| self.maybe_joined_flows.contains(flow))
+        {
+            self.maybe_joined_flows
+                .retain(|flow| !incoming.contains(flow));
+            return true;
         }
+        false
     }
 
-    pub async fn pop_current_flow(&self) -> Option<i32> {
-        self.inner.write().await.inner.current_flows.pop()
+    pub fn is_first_maybe_join(&self, incoming: &[i32]) -> bool {
+        incoming
+            .iter()
+            .filter(|flow| self.maybe_joined_flows.contains(flow))
+            .count()
+            == 1
     }
 
-    pub async fn push_current_flow(&self, flow: i32) {
-        self.inner.write().await.inner.current_flows.push(flow);
+    pub fn push_child(&mut self, task: i32, definition: Option<String>, child: State) {
+        self.children.push(ChildState {
+            task,
+            definition,
+            state: child,
+        });
     }
 
-    pub async fn pop_visited_flow(&self) -> Option<i32> {
-        self.inner.write().await.inner.visited_flows.pop()
+    pub fn take_child(&mut self, task: i32) -> Option<ChildState> {
+        let pos = self.children.iter().position(|child| child.task == task)?;
+        Some(self.children.remove(pos))
     }
 
-    pub async fn push_visited_flow(&self, flow: i32) {
-        self.inner.write().await.inner.visited_flows.push(flow);
+    pub fn child(&self, task: i32) -> Option<&State> {
+        self.children
+            .iter()
+            .find(|child| child.task == task)
+            .map(|child| &child.state)
     }
 
-    pub async fn pop_pending_task(&self) -> Option<i32> {
-        self.inner.write().await.inner.pending_tasks.pop()
+    pub fn child_mut(&mut self, task: i32) -> Option<&mut State> {
+        self.children
+            .iter_mut()
+            .find(|child| child.task == task)
+            .map(|child| &mut child.state)
     }
 
-    pub async fn push_pending_task(&self, task: i32) {
-        self.inner.write().await.inner.pending_tasks.push(task);
+    pub fn scope(&self, scope: &[i32]) -> Option<&State> {
+        match scope.split_first() {
+            Some((task, rest)) => self.child(*task).and_then(|child| child.scope(rest)),
+            None => Some(self),
+        }
     }
 
-    pub async fn pop_maybe_future_task(&self) -> Option<i32> {
-        self.inner.write().await.inner.maybe_future_tasks.pop()
+    pub fn scope_mut(&mut self, scope: &[i32]) -> Option<&mut State> {
+        match scope.split_first() {
+            Some((task, rest)) => self
+                .child_mut(*task)
+                .and_then(|child| child.scope_mut(rest)),
+            None => Some(self),
+        }
     }
+}
+
+pub fn serialize(state: &State) -> AlignedVec {
+    let mut serializer = AllocSerializer::<0>::default();
+    serializer.serialize_value(state).unwrap();
+    let mut data = serializer.into_serializer().into_inner();
+    data.extend_from_slice(STATE_FORMAT);
+    data
+}
 
-    pub async fn push_maybe_future_task(&self, task: i32) {
-        self.inner.write().await.inner.maybe_future_tasks.push(task);
+pub fn deserialize(data: &[u8]) -> Result<State, StateError> {
+    let current = data.strip_suffix(STATE_FORMAT);
+    let mut aligned = AlignedVec::new();
+    aligned.extend_from_slice(current.unwrap_or(data));
+    let mut deserializer = SharedDeserializeMap::default();
+    // the footer only tells which layout to expect, the bytes themselves may still be
+    // truncated or corrupted and are checked before they are read
+    if current.is_some() {
+        let archived = rkyv::check_archived_root::<State>(&aligned)
+            .map_err(|err| StateError::Invalid(err.to_string()))?;
+        return Ok(archived.deserialize(&mut deserializer)?);
     }
+    let archived = rkyv::check_archived_root::<legacy::State>(&aligned)
+        .map_err(|err| StateError::Invalid(err.to_string()))?;
+    let state: legacy::State = archived.deserialize(&mut deserializer)?;
+    Ok(state.into())
+}
+
+pub struct LockedState {
+    pub inner: State,
+}
+
+#[derive(Clone)]
+pub struct WorkflowState {
+    inner: Arc<RwLock<LockedState>>,
+}
 
-    pub async fn push_maybe_visited_task(&self, task: i32) {
-        self.inner
-            .write()
-            .await
-            .inner
-            .maybe_visited_tasks
-            .push(task);
+impl WorkflowState {
+    pub fn new(start_event: i32) -> Self {
+        Self::from_state(State::new(start_event))
+    }
+
+    pub fn new_in(start_event: i32, scope: &[i32]) -> Self {
+        Self::from_state(State::new_in(start_event, scope))
+    }
+
+    pub fn from_state(state: State) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(LockedState { inner: state })),
+        }
     }
 
-    pub async fn pop_maybe_future_flow(&self) -> Option<i32> {
-        self.inner.write().await.inner.maybe_future_flows.pop()
+    pub async fn replace(&self, state: State) {
+        self.inner.write().await.inner = state;
     }
 
-    pub async fn push_maybe_future_flow(&self, flow: i32) {
-        self.inner.write().await.inner.maybe_future_flows.push(flow);
+    pub async fn take(&self) -> State {
+        std::mem::replace(&mut self.inner.write().await.inner, State::new(-1))
     }
 
-    pub async fn pending_task_by_index(&self, idx: usize) -> i32 {
-        self.inner.write().await.inner.pending_tasks.remove(idx)
+    pub async fn set_remote_id(&self, remote_id: String, remote_version: i64) {
+        let mut state = self.inner.write().await;
+        state.inner.remote_id = Some(remote_id);
+        state.inner.remote_version = Some(remote_version);
+    }
+
+    pub async fn get_remote_id(&self) -> Option<String> {
+        self.inner.read().await.inner.remote_id.clone()
+    }
+
+    pub async fn has_visited(&self, user_task: i32) -> bool {
+        let state = self.inner.read().await;
+        state.inner.visited_tasks.contains(&user_task)
+    }
+
+    pub async fn has_maybe_visited(&self, user_task: i32) -> bool {
+        let state = self.inner.read().await;
+        state.inner.maybe_visited_tasks.contains(&user_task)
+    }
+
+    pub async fn set_active(&self, active: i32) {
+        self.inner.write().await.inner.active = active;
+    }
+
+    pub async fn get_active(&self) -> i32 {
+        self.inner.read().await.inner.active
+    }
+
+    pub async fn set_completed(&self) {
+        self.inner.write().await.inner.completed = true;
     }
 
     pub async fn state(&self) -> RwLockReadGuard<LockedState> {
@@ -0,0 +1,65 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+use super::Position;
+use crate::history::History;
+
+// layout of `State` in the 0.20.2 release, only kept to read archives written by it
+#[derive(Archive, Debug, Deserialize, Serialize)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    check_bytes
+)]
+pub struct State {
+    pub active: i32,
+    pub current_tasks: Vec<i32>,
+    pub current_flows: Vec<i32>,
+    pub visited_tasks: Vec<i32>,
+    pub visited_flows: Vec<i32>,
+    pub pending_tasks: Vec<i32>,
+    pub maybe_future_tasks: Vec<i32>,
+    pub maybe_future_flows: Vec<i32>,
+    pub maybe_visited_tasks: Vec<i32>,
+    pub variables: wfrs_model::json::JsonValue,
+    pub completed: bool,
+    pub remote_id: Option<String>,
+    pub remote_version: Option<i64>,
+}
+
+impl From<State> for super::State {
+    fn from(state: State) -> Self {
+        let mut migrated = Self {
+            active: state.active,
+            tokens: vec![],
+            next_token: 0,
+            step: 0,
+            visited_tasks: state.visited_tasks,
+            visited_flows: state.visited_flows,
+            maybe_future_tasks: state.maybe_future_tasks,
+            maybe_future_flows: state.maybe_future_flows,
+            maybe_visited_tasks: state.maybe_visited_tasks,
+            maybe_joined_flows: vec![],
+            variables: state.variables,
+            completed: state.completed,
+            remote_id: state.remote_id,
+            remote_version: state.remote_version,
+            children: vec![],
+            history: History::default(),
+        };
+        // the stacks were drained before an instance was stored, so in practice only
+        // the pending tasks carry over; their order is kept because the first one
+        // becomes the active task
+        let positions = state
+            .pending_tasks
+            .into_iter()
+            .map(Position::Waiting)
+            .chain(state.current_flows.into_iter().map(Position::Flow))
+            .chain(state.current_tasks.into_iter().map(Position::Task));
+        for position in positions {
+            migrated.spawn(position, &[]);
+        }
+        migrated
+    }
+}
@@ -0,0 +1,32 @@
+// every test binary compiles this module, not all of them use every helper
+#![allow(dead_code)]
+
+use wfrs_model::{WorkflowBundle, WorkflowDefinition};
+use wfrs_parser::{parse, ParseOptions};
+
+pub fn bundle(xml: &str) -> WorkflowBundle {
+    let parsed = parse(xml, &ParseOptions::default()).unwrap();
+    assert!(!parsed.has_errors(), "{:?}", parsed.issues());
+    parsed.bundle
+}
+
+// plain tasks only run as pass-throughs when parsing leniently
+pub fn lenient_bundle(xml: &str) -> WorkflowBundle {
+    let options = ParseOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    parse(xml, &options).unwrap().bundle
+}
+
+pub fn task(definition: &WorkflowDefinition, id: &str) -> i32 {
+    definition.task_by_id(id).unwrap().id
+}
+
+pub fn flow(definition: &WorkflowDefinition, id: &str) -> i32 {
+    let index = definition
+        .flow_ids
+        .iter()
+        .position(|flow| flow.as_ref() == id);
+    index.unwrap() as i32
+}
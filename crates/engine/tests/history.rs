@@ -0,0 +1,320 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use wfrs_engine::history::{Command, Event};
+use wfrs_engine::state::{deserialize, serialize, State};
+use wfrs_engine::{DivergenceKind, Engine, EngineError};
+use wfrs_model::json::JsonValue;
+use wfrs_validator::FunctionRegistry;
+
+mod common;
+
+use common::{bundle, flow, task};
+
+const REVIEW: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <bpmn:process id="review">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="draft">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:incoming>f4</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+    </bpmn:userTask>
+    <bpmn:exclusiveGateway id="decide" default="f3">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+      <bpmn:outgoing>f4</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:endEvent id="end"><bpmn:incoming>f3</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="draft" />
+    <bpmn:sequenceFlow id="f2" sourceRef="draft" targetRef="decide" />
+    <bpmn:sequenceFlow id="f3" sourceRef="decide" targetRef="end" />
+    <bpmn:sequenceFlow id="f4" sourceRef="decide" targetRef="draft">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$steps.draft.rejected == true</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+fn rejected(value: bool) -> JsonValue {
+    let mut variables = JsonValue::map();
+    variables.set_path("rejected", JsonValue::Bool(value));
+    variables
+}
+
+fn commands(state: &State) -> Vec<Command> {
+    state
+        .history
+        .entries
+        .iter()
+        .map(|entry| entry.command.clone())
+        .collect()
+}
+
+#[test]
+fn record() {
+    let bundle = bundle(REVIEW);
+    let definition = bundle.get("review").unwrap();
+    let draft = task(definition, "draft");
+    let engine = Engine::new(definition, "review".to_string());
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+    engine
+        .set_variables(&mut state, draft, rejected(true))
+        .unwrap();
+    engine.complete(&mut state, draft).unwrap();
+    // nothing is waiting on `end`, so this fails and is neither applied nor recorded
+    assert!(engine
+        .complete(&mut state, task(definition, "end"))
+        .is_err());
+
+    assert_eq!(
+        commands(&state),
+        vec![
+            Command::Run,
+            Command::SetVariables {
+                scope: vec![],
+                task: draft,
+                variables: rejected(true),
+            },
+            Command::Complete {
+                scope: vec![],
+                task: draft,
+            },
+        ]
+    );
+    let events = &state.history.entries[2].events;
+    assert_eq!(
+        events.first(),
+        Some(&Event::TaskCompleted {
+            scope: vec![],
+            task: draft,
+        })
+    );
+    assert!(events.contains(&Event::GatewayEvaluated {
+        scope: vec![],
+        gateway: task(definition, "decide"),
+        outgoing: vec![flow(definition, "f4")],
+    }));
+    assert_eq!(
+        events.last(),
+        Some(&Event::TaskActivated {
+            scope: vec![],
+            task: draft,
+        })
+    );
+    assert!(state.is_waiting(draft));
+
+    engine
+        .set_variables(&mut state, draft, rejected(false))
+        .unwrap();
+    engine.complete(&mut state, draft).unwrap();
+    assert!(state.completed);
+    assert_eq!(
+        state.history.entries[4].events.last(),
+        Some(&Event::Completed { scope: vec![] })
+    );
+
+    let restored = deserialize(&serialize(&state)).unwrap();
+    assert_eq!(commands(&restored), commands(&state));
+    assert!(restored.history.base.is_some());
+}
+
+#[test]
+fn undo() {
+    let bundle = bundle(REVIEW);
+    let definition = bundle.get("review").unwrap();
+    let draft = task(definition, "draft");
+    let engine = Engine::new(definition, "review".to_string());
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+    engine
+        .set_variables(&mut state, draft, rejected(false))
+        .unwrap();
+    engine.complete(&mut state, draft).unwrap();
+    assert!(state.completed);
+    state.remote_id = Some("remote".to_string());
+
+    engine.undo(&mut state, 2).unwrap();
+    assert!(!state.completed);
+    assert!(state.is_waiting(draft));
+    // the undone completion stays in the history, followed by the undo itself
+    assert_eq!(state.history.len(), 4);
+    assert_eq!(state.history.entries[3].command, Command::Undo { to: 2 });
+    assert_eq!(
+        state.history.entries[3].events,
+        vec![Event::Undone { to: 2 }]
+    );
+    assert_eq!(
+        state.variables.get_path("draft.rejected"),
+        Some(&JsonValue::Bool(false))
+    );
+    assert_eq!(state.remote_id.as_deref(), Some("remote"));
+
+    // undoing to the entry after the completion restores it
+    engine.undo(&mut state, 3).unwrap();
+    assert!(state.completed);
+    assert_eq!(state.history.len(), 5);
+
+    // replaying the history reproduces the undo
+    engine.undo(&mut state, 4).unwrap();
+    assert!(!state.completed);
+    let replayed = engine.replay(&state.history).unwrap();
+    assert!(replayed.is_waiting(draft));
+    assert_eq!(commands(&replayed), commands(&state));
+
+    engine.undo(&mut state, 0).unwrap();
+    assert_eq!(state.history.len(), 7);
+    assert_eq!(state.pending_tasks(), Vec::<i32>::new());
+    engine.run(&mut state).unwrap();
+    assert!(state.is_waiting(draft));
+
+    let err = engine.undo(&mut state, 100).unwrap_err();
+    assert!(matches!(err, EngineError::Rejected(_)), "{err}");
+    assert_eq!(state.history.len(), 8);
+}
+
+#[test]
+fn undo_rollback() {
+    let bundle = bundle(REVIEW);
+    let definition = bundle.get("review").unwrap();
+    let draft = task(definition, "draft");
+    let engine = Engine::new(definition, "review".to_string());
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+    engine
+        .set_variables(&mut state, draft, rejected(false))
+        .unwrap();
+    engine.complete(&mut state, draft).unwrap();
+    let before = serialize(&state);
+    let strict = Engine::new(definition, "review".to_string()).with_step_budget(4);
+    let mut rewound = state.clone();
+    strict
+        .execute(&mut rewound, Command::Undo { to: 2 })
+        .unwrap();
+    assert!(rewound.is_waiting(draft));
+
+    // rewinding fits the budget but the simulation after it does not, the undo must
+    // not stay behind on its own
+    let err = strict.undo(&mut state, 2).unwrap_err();
+    assert_eq!(err, EngineError::InfiniteLoop("decide".to_string()));
+    assert_eq!(serialize(&state).as_slice(), before.as_slice());
+    assert_eq!(state.history.len(), 3);
+}
+
+#[test]
+fn replay() {
+    let bundle = bundle(REVIEW);
+    let definition = bundle.get("review").unwrap();
+    let draft = task(definition, "draft");
+    // bare commands record the clock they are replayed at, keep it still to compare bytes
+    let engine =
+        Engine::new(definition, "review".to_string()).with_clock(JsonValue::Number(0.0.into()));
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+    engine
+        .set_variables(&mut state, draft, rejected(true))
+        .unwrap();
+    engine.complete(&mut state, draft).unwrap();
+    engine
+        .set_variables(&mut state, draft, rejected(false))
+        .unwrap();
+    engine.complete(&mut state, draft).unwrap();
+
+    let replayed = engine.replay(&state.history).unwrap();
+    assert_eq!(
+        serialize(&replayed).as_slice(),
+        serialize(&state).as_slice()
+    );
+    let replayed = engine.replay_commands(&commands(&state)).unwrap();
+    assert_eq!(
+        serialize(&replayed).as_slice(),
+        serialize(&state).as_slice()
+    );
+
+    // once the loop condition is flipped the first completion already ends the instance
+    let changed = common::bundle(&REVIEW.replace("== true", "== false"));
+    let changed = Engine::new(changed.get("review").unwrap(), "review".to_string());
+    let divergence = changed.replay(&state.history).unwrap_err();
+    assert_eq!(divergence.step, 2);
+    assert!(matches!(divergence.kind, DivergenceKind::Events { .. }));
+
+    let divergence = changed.replay_commands(&commands(&state)).unwrap_err();
+    assert_eq!(divergence.step, 3);
+    assert_eq!(
+        divergence.kind,
+        DivergenceKind::Failed(EngineError::Rejected(format!(
+            "task with id {draft} is not waiting"
+        )))
+    );
+}
+
+const DEADLINE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <bpmn:process id="deadline">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="submit"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:userTask>
+    <bpmn:exclusiveGateway id="late" default="f3">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+      <bpmn:outgoing>f4</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:userTask id="escalate"><bpmn:incoming>f4</bpmn:incoming><bpmn:outgoing>f5</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end">
+      <bpmn:incoming>f3</bpmn:incoming>
+      <bpmn:incoming>f5</bpmn:incoming>
+    </bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="submit" />
+    <bpmn:sequenceFlow id="f2" sourceRef="submit" targetRef="late" />
+    <bpmn:sequenceFlow id="f3" sourceRef="late" targetRef="end" />
+    <bpmn:sequenceFlow id="f4" sourceRef="late" targetRef="escalate">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">now() &gt; 1000 || $env.strict == true</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f5" sourceRef="escalate" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn replay_clock() {
+    let bundle = bundle(DEADLINE);
+    let definition = bundle.get("deadline").unwrap();
+    let submit = task(definition, "submit");
+    let clock = Arc::new(AtomicU64::new(500));
+    let functions = {
+        let clock = clock.clone();
+        FunctionRegistry::default().with("now", move |_: &[JsonValue]| {
+            Ok(JsonValue::Number(
+                (clock.load(Ordering::SeqCst) as f64).into(),
+            ))
+        })
+    };
+    let engine =
+        Engine::new(definition, "deadline".to_string()).with_functions(Arc::new(functions));
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+    engine.complete(&mut state, submit).unwrap();
+    assert!(state.completed);
+    assert_eq!(
+        state.history.entries[1].clock,
+        Some(JsonValue::Number(500.0.into()))
+    );
+
+    // the deadline has passed and the env changed since, neither affects the replay
+    clock.store(2000, Ordering::SeqCst);
+    let mut strict = JsonValue::map();
+    strict.set_path("strict", JsonValue::Bool(true));
+    let engine = engine.with_env(Arc::new(strict));
+    let replayed = engine.replay(&state.history).unwrap();
+    assert_eq!(
+        serialize(&replayed).as_slice(),
+        serialize(&state).as_slice()
+    );
+
+    // undoing and restoring the completion rewinds under the recorded clock as well
+    engine.undo(&mut state, 1).unwrap();
+    assert!(state.is_waiting(submit));
+    engine.undo(&mut state, 2).unwrap();
+    assert!(state.completed);
+
+    // commands without a recording run against the current clock
+    let replayed = engine.replay_commands(&commands(&state)[..2]).unwrap();
+    assert_eq!(replayed.pending_tasks(), vec![task(definition, "escalate")]);
+}
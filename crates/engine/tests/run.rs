@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use wfrs_engine::resolver::DefinitionResolver;
+use wfrs_engine::{Engine, EngineError, Runtime, MAX_CALL_DEPTH};
+use wfrs_model::{WorkflowBundle, WorkflowDefinition};
+
+mod common;
+
+use common::{lenient_bundle, task};
+
+const LOOP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="loop">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:exclusiveGateway id="again">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:incoming>f3</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:task id="work"><bpmn:incoming>f2</bpmn:incoming><bpmn:outgoing>f3</bpmn:outgoing></bpmn:task>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="again" />
+    <bpmn:sequenceFlow id="f2" sourceRef="again" targetRef="work" />
+    <bpmn:sequenceFlow id="f3" sourceRef="work" targetRef="again" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+const CHAIN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="chain">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:task id="first"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:task>
+    <bpmn:task id="second"><bpmn:incoming>f2</bpmn:incoming><bpmn:outgoing>f3</bpmn:outgoing></bpmn:task>
+    <bpmn:endEvent id="end"><bpmn:incoming>f3</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="first" />
+    <bpmn:sequenceFlow id="f2" sourceRef="first" targetRef="second" />
+    <bpmn:sequenceFlow id="f3" sourceRef="second" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+const RECURSIVE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="recursive">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:callActivity id="again" calledElement="recursive"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:callActivity>
+    <bpmn:endEvent id="end"><bpmn:incoming>f2</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="again" />
+    <bpmn:sequenceFlow id="f2" sourceRef="again" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+struct Bundle<'a>(&'a WorkflowBundle);
+
+impl<'a> DefinitionResolver<'a> for Bundle<'a> {
+    fn resolve(&self, called_element: &str) -> Option<&'a WorkflowDefinition> {
+        self.0.get(called_element)
+    }
+}
+
+#[test]
+fn infinite_loop() {
+    let bundle = lenient_bundle(LOOP);
+    let definition = bundle.get("loop").unwrap();
+    let runtime = Runtime::new(definition, "loop".to_string()).with_step_budget(100);
+    pollster::block_on(async {
+        let err = runtime.run().await.unwrap_err();
+        assert_eq!(err, EngineError::InfiniteLoop("again".to_string()));
+        assert_eq!(err.to_string(), "infinite loop detected at gateway again");
+        let err = runtime.simulate().await.unwrap_err();
+        assert_eq!(err, EngineError::InfiniteLoop("again".to_string()));
+    });
+}
+
+#[test]
+fn step_budget() {
+    let bundle = lenient_bundle(CHAIN);
+    let definition = bundle.get("chain").unwrap();
+    pollster::block_on(async {
+        // start, first, second and end each take a task and a flow token
+        let runtime = Runtime::new(definition, "chain".to_string()).with_step_budget(7);
+        runtime.run().await.unwrap();
+        assert!(runtime.instance.state().await.inner.completed);
+
+        let runtime = Runtime::new(definition, "chain".to_string()).with_step_budget(6);
+        let err = runtime.run().await.unwrap_err();
+        assert_eq!(err, EngineError::BudgetExhausted("end".to_string()));
+    });
+}
+
+#[test]
+fn sync_engine() {
+    let bundle = lenient_bundle(CHAIN);
+    let definition = bundle.get("chain").unwrap();
+    let engine = Engine::new(definition, "chain".to_string());
+    let mut state = engine.start();
+    let mut stuck = engine.start();
+    engine.run(&mut state).unwrap();
+    engine.simulate(&mut state).unwrap();
+    assert!(state.completed);
+    assert!(state.tokens.is_empty());
+    assert_eq!(state.visited_flows.len(), 3);
+
+    let err = engine.with_step_budget(2).run(&mut stuck).unwrap_err();
+    assert_eq!(err, EngineError::BudgetExhausted("first".to_string()));
+}
+
+#[test]
+fn call_activity() {
+    let bundle = lenient_bundle(RECURSIVE);
+    let definition = bundle.get("recursive").unwrap();
+    let engine = Engine::new(definition, "recursive".to_string());
+    let mut state = engine.start();
+    let err = engine.run(&mut state).unwrap_err();
+    assert_eq!(
+        err,
+        EngineError::UnresolvedCallActivity {
+            called_element: "recursive".to_string(),
+        }
+    );
+    // the failed run is rolled back, the start event can still be retried
+    assert_eq!(state.current_tasks(), vec![task(definition, "start")]);
+
+    let engine = engine.with_resolver(Arc::new(Bundle(&bundle)));
+    let err = engine.run(&mut state).unwrap_err();
+    assert_eq!(err, EngineError::CallDepthExceeded("again".to_string()));
+    assert!(state.children.is_empty());
+
+    // every nested process draws from the same budget
+    let err = engine
+        .with_step_budget(MAX_CALL_DEPTH)
+        .run(&mut state)
+        .unwrap_err();
+    assert_eq!(err, EngineError::BudgetExhausted("again".to_string()));
+}
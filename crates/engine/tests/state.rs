@@ -0,0 +1,384 @@
+use std::sync::Arc;
+use wfrs_engine::state::{deserialize, serialize, Position, State};
+use wfrs_engine::{Engine, Runtime};
+use wfrs_model::json::JsonValue;
+
+mod common;
+
+use common::{bundle, flow, task};
+
+// start -> review -> end, the process the checked in legacy archive was written for
+const LINEAR: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:camunda="http://camunda.org/schema/1.0/bpmn">
+  <bpmn:process id="linear" camunda:versionTag="1.0.0">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="review"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end"><bpmn:incoming>f2</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="review" />
+    <bpmn:sequenceFlow id="f2" sourceRef="review" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+const PARALLEL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:camunda="http://camunda.org/schema/1.0/bpmn">
+  <bpmn:process id="parallel" camunda:versionTag="1.0.0">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:parallelGateway id="split">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+    </bpmn:parallelGateway>
+    <bpmn:userTask id="left"><bpmn:incoming>f2</bpmn:incoming><bpmn:outgoing>f4</bpmn:outgoing></bpmn:userTask>
+    <bpmn:userTask id="right"><bpmn:incoming>f3</bpmn:incoming><bpmn:outgoing>f5</bpmn:outgoing></bpmn:userTask>
+    <bpmn:parallelGateway id="join">
+      <bpmn:incoming>f4</bpmn:incoming>
+      <bpmn:incoming>f5</bpmn:incoming>
+      <bpmn:outgoing>f6</bpmn:outgoing>
+    </bpmn:parallelGateway>
+    <bpmn:endEvent id="end"><bpmn:incoming>f6</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="split" />
+    <bpmn:sequenceFlow id="f2" sourceRef="split" targetRef="left" />
+    <bpmn:sequenceFlow id="f3" sourceRef="split" targetRef="right" />
+    <bpmn:sequenceFlow id="f4" sourceRef="left" targetRef="join" />
+    <bpmn:sequenceFlow id="f5" sourceRef="right" targetRef="join" />
+    <bpmn:sequenceFlow id="f6" sourceRef="join" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn migrate_legacy_archive() {
+    // written by the 0.20.2 release after starting `LINEAR`, setting two variables
+    // and a remote id, in the same way as `serialize_entry` of the wasm runtime
+    let data = include_bytes!("legacy_state.bin");
+    let bundle = bundle(LINEAR);
+    let definition = bundle.get("linear").unwrap();
+    let review = task(definition, "review");
+
+    let mut state = deserialize(data).unwrap();
+    let tokens: Vec<(Position, &[i32])> = state
+        .tokens
+        .iter()
+        .map(|token| (token.position, token.scope.as_slice()))
+        .collect();
+    assert_eq!(tokens, vec![(Position::Waiting(review), &[][..])]);
+    assert_eq!(state.active, review);
+    assert_eq!(state.visited_tasks, vec![task(definition, "start"), review]);
+    assert_eq!(state.visited_flows, vec![flow(definition, "f1")]);
+    assert_eq!(state.remote_id.as_deref(), Some("remote-1"));
+    assert_eq!(state.remote_version, Some(7));
+    let variables = state.variables.as_object().unwrap();
+    assert_eq!(variables.len(), 2);
+    assert_eq!(variables["applicant"].as_str(), Some("Ada"));
+    assert_eq!(variables["amount"].as_u64(), Some(3));
+    assert!(!state.completed);
+
+    // the migrated instance carries on where the old engine stopped
+    let engine = Engine::new(definition, "legacy".to_string());
+    engine.complete(&mut state, review).unwrap();
+    assert!(state.completed);
+
+    // truncated archives are rejected instead of being read out of bounds
+    assert!(deserialize(&data[..data.len() - 8]).is_err());
+}
+
+#[test]
+fn round_trip() {
+    let mut state = State::new(0);
+    state.next_token();
+    state.spawn(Position::Waiting(2), &[]);
+    state.spawn(Position::Joined(4), &[]);
+
+    let data = serialize(&state);
+    let state = deserialize(&data).unwrap();
+    assert_eq!(state.step, 1);
+    assert_eq!(state.next_token, 3);
+    assert_eq!(state.pending_tasks(), vec![2]);
+    assert_eq!(state.joined_flows(), vec![4]);
+    assert_eq!(state.tokens[1].created, 1);
+
+    // the footer is intact but the root points out of the archive
+    let mut corrupted = data.to_vec();
+    let footer = corrupted.len() - 8;
+    corrupted[footer - 64..footer].fill(0xff);
+    assert!(deserialize(&corrupted).is_err());
+    let mut truncated = data[..footer - 16].to_vec();
+    truncated.extend_from_slice(&data[footer..]);
+    assert!(deserialize(&truncated).is_err());
+}
+
+#[test]
+fn parallel_join() {
+    let bundle = bundle(PARALLEL);
+    let definition = bundle.get("parallel").unwrap();
+    let runtime = Runtime::new(definition, "parallel".to_string());
+    pollster::block_on(async {
+        runtime.run().await.unwrap();
+        let mut pending = runtime.instance.state().await.inner.pending_tasks();
+        pending.sort();
+        let mut expected = vec![task(definition, "left"), task(definition, "right")];
+        expected.sort();
+        assert_eq!(pending, expected);
+
+        runtime.complete(task(definition, "left")).await.unwrap();
+        {
+            let state = runtime.instance.state().await;
+            assert_eq!(state.inner.pending_tasks(), vec![task(definition, "right")]);
+            assert_eq!(state.inner.joined_flows().len(), 1);
+            assert!(!state.inner.completed);
+        }
+
+        runtime.complete(task(definition, "right")).await.unwrap();
+        let state = runtime.instance.state().await;
+        assert!(state.inner.completed);
+        assert!(state.inner.tokens.is_empty());
+        assert!(state
+            .inner
+            .visited_tasks
+            .contains(&task(definition, "join")));
+    });
+}
+
+const INCLUSIVE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <bpmn:process id="inclusive">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="form"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:userTask>
+    <bpmn:inclusiveGateway id="split">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+      <bpmn:outgoing>f4</bpmn:outgoing>
+    </bpmn:inclusiveGateway>
+    <bpmn:userTask id="left"><bpmn:incoming>f3</bpmn:incoming><bpmn:outgoing>f5</bpmn:outgoing></bpmn:userTask>
+    <bpmn:userTask id="right"><bpmn:incoming>f4</bpmn:incoming><bpmn:outgoing>f6</bpmn:outgoing></bpmn:userTask>
+    <bpmn:inclusiveGateway id="join">
+      <bpmn:incoming>f5</bpmn:incoming>
+      <bpmn:incoming>f6</bpmn:incoming>
+      <bpmn:outgoing>f7</bpmn:outgoing>
+    </bpmn:inclusiveGateway>
+    <bpmn:endEvent id="end"><bpmn:incoming>f7</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="form" />
+    <bpmn:sequenceFlow id="f2" sourceRef="form" targetRef="split" />
+    <bpmn:sequenceFlow id="f3" sourceRef="split" targetRef="left">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$steps.form.left == true</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f4" sourceRef="split" targetRef="right">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$steps.form.right == true</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f5" sourceRef="left" targetRef="join" />
+    <bpmn:sequenceFlow id="f6" sourceRef="right" targetRef="join" />
+    <bpmn:sequenceFlow id="f7" sourceRef="join" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+fn branches(left: bool, right: bool) -> JsonValue {
+    let mut variables = JsonValue::map();
+    variables.set_path("left", JsonValue::Bool(left));
+    variables.set_path("right", JsonValue::Bool(right));
+    variables
+}
+
+#[test]
+fn inclusive_join() {
+    let bundle = bundle(INCLUSIVE);
+    let definition = bundle.get("inclusive").unwrap();
+    let (form, left, right) = (
+        task(definition, "form"),
+        task(definition, "left"),
+        task(definition, "right"),
+    );
+    let engine = Engine::new(definition, "inclusive".to_string());
+
+    // a single taken branch passes the join as soon as it arrives
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+    engine
+        .set_variables(&mut state, form, branches(true, false))
+        .unwrap();
+    engine.complete(&mut state, form).unwrap();
+    assert_eq!(state.pending_tasks(), vec![left]);
+    engine.complete(&mut state, left).unwrap();
+    assert!(state.completed);
+
+    // with both branches taken the join waits for the second one
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+    engine
+        .set_variables(&mut state, form, branches(true, true))
+        .unwrap();
+    engine.complete(&mut state, form).unwrap();
+    let mut pending = state.pending_tasks();
+    pending.sort();
+    assert_eq!(pending, vec![left, right]);
+    engine.complete(&mut state, right).unwrap();
+    assert!(!state.completed);
+    assert_eq!(state.joined_flows(), vec![flow(definition, "f6")]);
+    engine.complete(&mut state, left).unwrap();
+    assert!(state.completed);
+    assert!(state.tokens.is_empty());
+}
+
+const SUB_PROCESS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="outer">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:subProcess id="sub">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+      <bpmn:startEvent id="inner_start"><bpmn:outgoing>s1</bpmn:outgoing></bpmn:startEvent>
+      <bpmn:userTask id="inner"><bpmn:incoming>s1</bpmn:incoming><bpmn:outgoing>s2</bpmn:outgoing></bpmn:userTask>
+      <bpmn:endEvent id="inner_end"><bpmn:incoming>s2</bpmn:incoming></bpmn:endEvent>
+      <bpmn:sequenceFlow id="s1" sourceRef="inner_start" targetRef="inner" />
+      <bpmn:sequenceFlow id="s2" sourceRef="inner" targetRef="inner_end" />
+    </bpmn:subProcess>
+    <bpmn:userTask id="after"><bpmn:incoming>f2</bpmn:incoming><bpmn:outgoing>f3</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end"><bpmn:incoming>f3</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="sub" />
+    <bpmn:sequenceFlow id="f2" sourceRef="sub" targetRef="after" />
+    <bpmn:sequenceFlow id="f3" sourceRef="after" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn sub_process_scope() {
+    let bundle = bundle(SUB_PROCESS);
+    let definition = bundle.get("outer").unwrap();
+    let sub = task(definition, "sub");
+    let inner = definition.sub_process(sub).unwrap();
+    let inner_task = task(inner, "inner");
+    let engine = Engine::new(definition, "outer".to_string());
+    let mut state = engine.start();
+    engine.run(&mut state).unwrap();
+
+    let positions: Vec<Position> = state.tokens.iter().map(|token| token.position).collect();
+    assert_eq!(positions, vec![Position::Child(sub)]);
+    let child = state.child(sub).unwrap();
+    assert_eq!(child.pending_tasks(), vec![inner_task]);
+    assert!(child.tokens.iter().all(|token| token.scope == vec![sub]));
+
+    // scoped tasks are not waiting at the root, they are addressed through their scope
+    let mut approved = JsonValue::map();
+    approved.set_path("approved", JsonValue::Bool(true));
+    assert!(engine
+        .set_variables(&mut state, inner_task, approved.clone())
+        .is_err());
+    engine
+        .set_variables_in(&mut state, &[sub], inner_task, approved)
+        .unwrap();
+    engine.complete_in(&mut state, &[sub], inner_task).unwrap();
+
+    assert!(state.child(sub).is_none());
+    assert_eq!(state.pending_tasks(), vec![task(definition, "after")]);
+    assert_eq!(
+        state.variables.get_path("sub.inner.approved"),
+        Some(&JsonValue::Bool(true))
+    );
+}
+
+const NESTED_CONDITION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <bpmn:process id="nested">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="form"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:userTask>
+    <bpmn:exclusiveGateway id="decide" default="f3">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+      <bpmn:outgoing>f4</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:userTask id="review"><bpmn:incoming>f4</bpmn:incoming><bpmn:outgoing>f5</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end">
+      <bpmn:incoming>f3</bpmn:incoming>
+      <bpmn:incoming>f5</bpmn:incoming>
+    </bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="form" />
+    <bpmn:sequenceFlow id="f2" sourceRef="form" targetRef="decide" />
+    <bpmn:sequenceFlow id="f3" sourceRef="decide" targetRef="end" />
+    <bpmn:sequenceFlow id="f4" sourceRef="decide" targetRef="review">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">($steps.form.amount &gt; 100 &amp;&amp; !$steps.form.trusted) || ($steps.form.priority ? $steps.form.priority == 'high' : false)</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f5" sourceRef="review" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn nested_condition() {
+    let bundle = bundle(NESTED_CONDITION);
+    let definition = bundle.get("nested").unwrap();
+    let form = task(definition, "form");
+    let engine = Engine::new(definition, "nested".to_string());
+    let takes_review = |amount: f64, trusted: bool, priority: Option<&str>| {
+        let mut variables = JsonValue::map();
+        variables.set_path("amount", JsonValue::Number(amount.into()));
+        variables.set_path("trusted", JsonValue::Bool(trusted));
+        if let Some(priority) = priority {
+            variables.set_path("priority", JsonValue::String(priority.to_string()));
+        }
+        let mut state = engine.start();
+        engine.run(&mut state).unwrap();
+        engine.set_variables(&mut state, form, variables).unwrap();
+        engine.complete(&mut state, form).unwrap();
+        state.pending_tasks() == vec![task(definition, "review")]
+    };
+    assert!(takes_review(500.0, false, None));
+    assert!(!takes_review(500.0, true, None));
+    assert!(!takes_review(50.0, false, None));
+    assert!(takes_review(50.0, true, Some("high")));
+    assert!(!takes_review(50.0, true, Some("low")));
+}
+
+const ROOTS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <bpmn:process id="roots">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:exclusiveGateway id="route" default="f2">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:userTask id="standard"><bpmn:incoming>f2</bpmn:incoming><bpmn:outgoing>f4</bpmn:outgoing></bpmn:userTask>
+    <bpmn:userTask id="express"><bpmn:incoming>f3</bpmn:incoming><bpmn:outgoing>f5</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end">
+      <bpmn:incoming>f4</bpmn:incoming>
+      <bpmn:incoming>f5</bpmn:incoming>
+    </bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="route" />
+    <bpmn:sequenceFlow id="f2" sourceRef="route" targetRef="standard" />
+    <bpmn:sequenceFlow id="f3" sourceRef="route" targetRef="express">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$vars.express == true &amp;&amp; $env.region == 'eu' &amp;&amp; $instance.entity_id == 'order-1'</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f4" sourceRef="standard" targetRef="end" />
+    <bpmn:sequenceFlow id="f5" sourceRef="express" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+fn express() -> JsonValue {
+    let mut variables = JsonValue::map();
+    variables.set_path("express", JsonValue::Bool(true));
+    variables
+}
+
+#[test]
+fn variable_roots() {
+    let bundle = bundle(ROOTS);
+    let definition = bundle.get("roots").unwrap();
+    let mut eu = JsonValue::map();
+    eu.set_path("region", JsonValue::String("eu".to_string()));
+    let route = |entity_id: &str, env: &JsonValue, process_variables: Option<JsonValue>| {
+        let engine = Engine::new(definition, entity_id.to_string()).with_env(Arc::new(env.clone()));
+        let mut state = engine.start();
+        if let Some(variables) = process_variables {
+            engine
+                .set_process_variables(&mut state, variables.clone())
+                .unwrap();
+            assert_eq!(engine.process_variables(&state), variables);
+        }
+        engine.run(&mut state).unwrap();
+        definition.task_ids[state.pending_tasks()[0] as usize].to_string()
+    };
+    assert_eq!(route("order-1", &eu, Some(express())), "express");
+    assert_eq!(route("order-1", &eu, None), "standard");
+    assert_eq!(route("order-2", &eu, Some(express())), "standard");
+    assert_eq!(
+        route("order-1", &JsonValue::map(), Some(express())),
+        "standard"
+    );
+}
@@ -3,6 +3,10 @@ use std::{str::FromStr, sync::Arc};
 
 use crate::json::JsonValue;
 
+mod parser;
+mod print;
+pub use parser::{parse, ParseError};
+
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 #[archive(compare(PartialEq))]
 #[archive_attr(derive(Debug))]
@@ -13,8 +17,15 @@ pub enum Operator {
     GreaterOrEqual,
     Lower,
     LowerOrEqual,
+    StrictEqual,
+    StrictNotEqual,
     And,
     Or,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
 }
 
 impl FromStr for Operator {
@@ -23,17 +34,45 @@ impl FromStr for Operator {
         match s {
             "==" => Ok(Operator::Equal),
             "!=" => Ok(Operator::NotEqual),
+            "===" => Ok(Operator::StrictEqual),
+            "!==" => Ok(Operator::StrictNotEqual),
             ">" => Ok(Operator::Greater),
             ">=" => Ok(Operator::GreaterOrEqual),
             "<" => Ok(Operator::Lower),
             "<=" => Ok(Operator::LowerOrEqual),
             "&&" => Ok(Operator::And),
             "||" => Ok(Operator::Or),
+            "+" => Ok(Operator::Add),
+            "-" => Ok(Operator::Sub),
+            "*" => Ok(Operator::Mul),
+            "/" => Ok(Operator::Div),
+            "%" => Ok(Operator::Mod),
             _ => Err(format!("Invalid operator '{s}'")),
         }
     }
 }
 
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub enum UnaryOperator {
+    Not,
+    Negate,
+    Plus,
+}
+
+impl FromStr for UnaryOperator {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "!" => Ok(UnaryOperator::Not),
+            "-" => Ok(UnaryOperator::Negate),
+            "+" => Ok(UnaryOperator::Plus),
+            _ => Err(format!("Invalid unary operator '{s}'")),
+        }
+    }
+}
+
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 #[archive(
     bound(
@@ -51,6 +90,38 @@ pub struct BinaryExpression {
     pub right: Box<JsepNode>,
 }
 
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    compare(PartialEq)
+)]
+#[archive_attr(derive(Debug))]
+pub struct UnaryExpression {
+    pub operator: UnaryOperator,
+    pub prefix: bool,
+    #[omit_bounds]
+    pub argument: Box<JsepNode>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(
+    bound(
+        serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
+        deserialize = "__D: rkyv::de::SharedDeserializeRegistry"
+    ),
+    compare(PartialEq)
+)]
+#[archive_attr(derive(Debug))]
+pub struct CallExpression {
+    #[omit_bounds]
+    pub callee: Box<JsepNode>,
+    #[omit_bounds]
+    pub arguments: Vec<JsepNode>,
+}
+
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 #[archive(compare(PartialEq))]
 #[archive_attr(derive(Debug))]
@@ -113,6 +184,8 @@ pub struct MemberExpression {
 pub enum JsepNode {
     ConditionalExpression(ConditionalExpression),
     BinaryExpression(BinaryExpression),
+    UnaryExpression(UnaryExpression),
+    CallExpression(CallExpression),
     Identifier(ExpressionIdentifier),
     Literal(ExpressionLiteral),
     MemberExpression(MemberExpression),
@@ -0,0 +1,466 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::{
+    BinaryExpression, CallExpression, ConditionalExpression, ExpressionIdentifier,
+    ExpressionLiteral, JsepNode, MemberExpression, Operator, UnaryExpression, UnaryOperator,
+};
+use crate::json::{JsonNumber, JsonValue};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub index: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at character {}", self.message, self.index)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// punctuators known to jsep, longest first so that `===` wins over `==`
+const PUNCTUATORS: [&str; 32] = [
+    ">>>", "===", "!==", "?.", "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<",
+    ">", "+", "-", "*", "/", "%", "!", "~", "?", ":", ".", ",", "(", ")", "[", "]",
+];
+
+const UNARY_OPERATORS: [&str; 4] = ["-", "!", "~", "+"];
+
+// jsep's default binary precedences, all left associative
+fn binary_precedence(operator: &str) -> Option<u8> {
+    match operator {
+        "||" => Some(1),
+        "&&" => Some(2),
+        "|" => Some(3),
+        "^" => Some(4),
+        "&" => Some(5),
+        "==" | "!=" | "===" | "!==" => Some(6),
+        "<" | ">" | "<=" | ">=" => Some(7),
+        "<<" | ">>" | ">>>" => Some(8),
+        "+" | "-" => Some(9),
+        "*" | "/" | "%" => Some(10),
+        _ => None,
+    }
+}
+
+pub fn parse(source: &str) -> Result<JsepNode, ParseError> {
+    let tokens = Tokenizer::new(source).tokenize()?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+    if parser.peek().kind == TokenKind::End {
+        return Err(parser.error("Empty expression"));
+    }
+    let node = parser.expression()?;
+    match &parser.peek().kind {
+        TokenKind::End => Ok(node),
+        _ => Err(parser.unexpected()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    String(String),
+    Identifier(String),
+    Punctuator(&'static str),
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    first: Option<char>,
+}
+
+struct Tokenizer {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Tokenizer {
+    fn new(source: &str) -> Self {
+        Tokenizer {
+            chars: source.chars().collect(),
+            index: 0,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            index: self.index,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(offset, ch)| self.peek_at(offset) == Some(ch))
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>, ParseError> {
+        let mut tokens = Vec::new();
+        loop {
+            while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+                self.index += 1;
+            }
+            let start = self.index;
+            let first = self.peek();
+            let kind = match first {
+                None => TokenKind::End,
+                Some(ch) if ch.is_ascii_digit() => self.number()?,
+                Some('.') if self.peek_at(1).is_some_and(|ch| ch.is_ascii_digit()) => {
+                    self.number()?
+                }
+                Some(quote @ ('"' | '\'')) => self.string(quote)?,
+                Some(ch) if is_identifier_start(ch) => {
+                    while self.peek().is_some_and(is_identifier_part) {
+                        self.index += 1;
+                    }
+                    TokenKind::Identifier(self.chars[start..self.index].iter().collect())
+                }
+                Some(ch) => self
+                    .punctuator()
+                    .ok_or_else(|| self.error(format!("Unexpected \"{ch}\"")))?,
+            };
+            let end = kind == TokenKind::End;
+            tokens.push(Token { kind, start, first });
+            if end {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn punctuator(&mut self) -> Option<TokenKind> {
+        let punctuator = PUNCTUATORS
+            .iter()
+            .find(|punctuator| self.starts_with(punctuator))
+            .copied()?;
+        // `a?.5:1` is a ternary, not an optional member
+        let punctuator = match punctuator {
+            "?." if self.peek_at(2).is_some_and(|ch| ch.is_ascii_digit()) => "?",
+            punctuator => punctuator,
+        };
+        self.index += punctuator.len();
+        Some(TokenKind::Punctuator(punctuator))
+    }
+
+    fn digits(&mut self, number: &mut String) {
+        while let Some(ch) = self.peek().filter(char::is_ascii_digit) {
+            number.push(ch);
+            self.index += 1;
+        }
+    }
+
+    fn number(&mut self) -> Result<TokenKind, ParseError> {
+        let mut number = String::new();
+        self.digits(&mut number);
+        if self.peek() == Some('.') {
+            number.push('.');
+            self.index += 1;
+            self.digits(&mut number);
+        }
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            number.push(e);
+            self.index += 1;
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                number.push(sign);
+                self.index += 1;
+            }
+            let exponent = number.len();
+            self.digits(&mut number);
+            if number.len() == exponent {
+                let next = self.peek().map(String::from).unwrap_or_default();
+                return Err(self.error(format!("Expected exponent ({number}{next})")));
+            }
+        }
+        match self.peek() {
+            Some(ch) if is_identifier_start(ch) => Err(self.error(format!(
+                "Variable names cannot start with a number ({number}{ch})"
+            ))),
+            Some('.') => Err(self.error("Unexpected period")),
+            // `1e999` would overflow into a literal that cannot be printed back
+            _ => number
+                .parse()
+                .ok()
+                .filter(|value: &f64| value.is_finite())
+                .map(TokenKind::Number)
+                .ok_or_else(|| self.error(format!("Invalid number ({number})"))),
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Result<TokenKind, ParseError> {
+        self.index += 1;
+        let mut value = String::new();
+        loop {
+            let Some(ch) = self.peek() else {
+                return Err(self.error(format!("Unclosed quote after \"{value}\"")));
+            };
+            self.index += 1;
+            match ch {
+                ch if ch == quote => return Ok(TokenKind::String(value)),
+                '\\' => {
+                    let Some(escaped) = self.peek() else {
+                        continue;
+                    };
+                    self.index += 1;
+                    value.push(match escaped {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'v' => '\u{b}',
+                        other => other,
+                    });
+                }
+                ch => value.push(ch),
+            }
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            index: self.peek().start,
+        }
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek().first {
+            Some(ch) => self.error(format!("Unexpected \"{ch}\"")),
+            None => self.error("Expected expression"),
+        }
+    }
+
+    fn at(&self, punctuator: &str) -> bool {
+        matches!(self.peek().kind, TokenKind::Punctuator(symbol) if symbol == punctuator)
+    }
+
+    fn expect(&mut self, punctuator: &str, message: &str) -> Result<(), ParseError> {
+        if !self.at(punctuator) {
+            return Err(self.error(message));
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<JsepNode, ParseError> {
+        let test = self.binary(0)?;
+        if !self.at("?") {
+            return Ok(test);
+        }
+        self.next();
+        let consequent = self.expression()?;
+        self.expect(":", "Expected :")?;
+        let alternate = self.expression()?;
+        Ok(JsepNode::ConditionalExpression(ConditionalExpression {
+            test: Box::new(test),
+            consequent: Box::new(consequent),
+            alternate: Box::new(alternate),
+        }))
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Result<JsepNode, ParseError> {
+        let mut left = self.unary()?;
+        loop {
+            let TokenKind::Punctuator(symbol) = self.peek().kind else {
+                return Ok(left);
+            };
+            let Some(precedence) = binary_precedence(symbol) else {
+                return Ok(left);
+            };
+            if precedence <= min_precedence {
+                return Ok(left);
+            }
+            let operator = Operator::from_str(symbol)
+                .map_err(|_| self.error(format!("Unsupported operator '{symbol}'")))?;
+            self.next();
+            if self.peek().kind == TokenKind::End {
+                return Err(self.error(format!("Expected expression after {symbol}")));
+            }
+            let right = self.binary(precedence)?;
+            left = JsepNode::BinaryExpression(BinaryExpression {
+                operator,
+                left: Box::new(left),
+                right: Box::new(right),
+            });
+        }
+    }
+
+    fn unary(&mut self) -> Result<JsepNode, ParseError> {
+        match self.peek().kind {
+            TokenKind::Punctuator(symbol) if UNARY_OPERATORS.contains(&symbol) => {
+                let operator = UnaryOperator::from_str(symbol)
+                    .map_err(|_| self.error(format!("Unsupported operator '{symbol}'")))?;
+                self.next();
+                if self.peek().kind == TokenKind::End {
+                    return Err(self.error("missing unaryOp argument"));
+                }
+                let argument = self.unary()?;
+                Ok(JsepNode::UnaryExpression(UnaryExpression {
+                    operator,
+                    prefix: true,
+                    argument: Box::new(argument),
+                }))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<JsepNode, ParseError> {
+        let mut node = self.primary()?;
+        loop {
+            let optional = self.at("?.");
+            if optional {
+                self.next();
+            }
+            node = match self.peek().kind {
+                TokenKind::Punctuator(".") if !optional => {
+                    self.next();
+                    self.member(node, false)?
+                }
+                TokenKind::Punctuator("[") => {
+                    self.next();
+                    let property = self.expression()?;
+                    self.expect("]", "Unclosed [")?;
+                    JsepNode::MemberExpression(MemberExpression {
+                        computed: true,
+                        optional,
+                        object: Box::new(node),
+                        property: Box::new(property),
+                    })
+                }
+                TokenKind::Punctuator("(") if optional => {
+                    return Err(self.error("Optional calls are not supported"));
+                }
+                TokenKind::Punctuator("(") => {
+                    self.next();
+                    JsepNode::CallExpression(CallExpression {
+                        callee: Box::new(node),
+                        arguments: self.arguments()?,
+                    })
+                }
+                _ if optional => self.member(node, true)?,
+                _ => return Ok(node),
+            };
+        }
+    }
+
+    fn member(&mut self, object: JsepNode, optional: bool) -> Result<JsepNode, ParseError> {
+        let TokenKind::Identifier(name) = self.peek().kind.clone() else {
+            return Err(self.unexpected());
+        };
+        self.next();
+        Ok(JsepNode::MemberExpression(MemberExpression {
+            computed: false,
+            optional,
+            object: Box::new(object),
+            property: Box::new(identifier(name)),
+        }))
+    }
+
+    fn arguments(&mut self) -> Result<Vec<JsepNode>, ParseError> {
+        let mut arguments = Vec::new();
+        if self.at(")") {
+            self.next();
+            return Ok(arguments);
+        }
+        loop {
+            if self.at(",") {
+                return Err(self.error("Unexpected token ,"));
+            }
+            arguments.push(self.expression()?);
+            match self.peek().kind {
+                TokenKind::Punctuator(",") => {
+                    self.next();
+                }
+                TokenKind::Punctuator(")") => {
+                    self.next();
+                    return Ok(arguments);
+                }
+                TokenKind::End => return Err(self.error("Expected )")),
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<JsepNode, ParseError> {
+        let token = self.peek().clone();
+        let node = match token.kind {
+            TokenKind::Number(value) => literal(JsonValue::Number(JsonNumber::from(value))),
+            TokenKind::String(value) => literal(JsonValue::String(value)),
+            TokenKind::Identifier(name) => match name.as_str() {
+                "true" => literal(JsonValue::Bool(true)),
+                "false" => literal(JsonValue::Bool(false)),
+                "null" => literal(JsonValue::Null),
+                "this" => return Err(self.error("this is not supported")),
+                _ => identifier(name),
+            },
+            TokenKind::Punctuator("(") => {
+                self.next();
+                let node = self.expression()?;
+                self.expect(")", "Unclosed (")?;
+                return Ok(node);
+            }
+            TokenKind::Punctuator("[") => {
+                return Err(self.error("Array expressions are not supported"))
+            }
+            _ => return Err(self.unexpected()),
+        };
+        self.next();
+        Ok(node)
+    }
+}
+
+fn identifier(name: String) -> JsepNode {
+    JsepNode::Identifier(ExpressionIdentifier {
+        name: Arc::from(name),
+    })
+}
+
+fn literal(value: JsonValue) -> JsepNode {
+    JsepNode::Literal(ExpressionLiteral { value })
+}
+
+fn is_identifier_start(ch: char) -> bool {
+    ch == '$' || ch == '_' || ch.is_ascii_alphabetic() || (ch as u32) >= 128
+}
+
+fn is_identifier_part(ch: char) -> bool {
+    is_identifier_start(ch) || ch.is_ascii_digit()
+}
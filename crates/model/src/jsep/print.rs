@@ -0,0 +1,181 @@
+use std::fmt::{self, Write};
+
+use super::{JsepNode, Operator, UnaryOperator};
+use crate::json::{JsonNumber, JsonValue};
+
+const CONDITIONAL: u8 = 0;
+const UNARY: u8 = 11;
+const POSTFIX: u8 = 12;
+const PRIMARY: u8 = 13;
+
+impl Operator {
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::StrictEqual
+            | Operator::StrictNotEqual => 6,
+            Operator::Greater
+            | Operator::GreaterOrEqual
+            | Operator::Lower
+            | Operator::LowerOrEqual => 7,
+            Operator::Add | Operator::Sub => 9,
+            Operator::Mul | Operator::Div | Operator::Mod => 10,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::StrictEqual => "===",
+            Operator::StrictNotEqual => "!==",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::Lower => "<",
+            Operator::LowerOrEqual => "<=",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Mod => "%",
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl UnaryOperator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnaryOperator::Not => "!",
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Plus => "+",
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl JsepNode {
+    fn precedence(&self) -> u8 {
+        match self {
+            JsepNode::ConditionalExpression(_) => CONDITIONAL,
+            JsepNode::BinaryExpression(node) => node.operator.precedence(),
+            JsepNode::UnaryExpression(_) => UNARY,
+            JsepNode::CallExpression(_) | JsepNode::MemberExpression(_) => POSTFIX,
+            // `-1` prints like a unary expression and `1.x` would not parse
+            JsepNode::Literal(literal) => match &literal.value {
+                JsonValue::Number(_) => UNARY,
+                _ => PRIMARY,
+            },
+            JsepNode::Identifier(_) => PRIMARY,
+        }
+    }
+}
+
+// wrap `node` in parentheses when it binds looser than its position requires
+fn write_operand(f: &mut impl Write, node: &JsepNode, min_precedence: u8) -> fmt::Result {
+    if node.precedence() < min_precedence {
+        write!(f, "({node})")
+    } else {
+        write!(f, "{node}")
+    }
+}
+
+fn write_string(f: &mut impl Write, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in value.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{c}' => f.write_str("\\f")?,
+            '\u{b}' => f.write_str("\\v")?,
+            ch => f.write_char(ch)?,
+        }
+    }
+    f.write_char('"')
+}
+
+fn write_literal(f: &mut impl Write, value: &JsonValue) -> fmt::Result {
+    match value {
+        JsonValue::Null => f.write_str("null"),
+        JsonValue::Bool(value) => write!(f, "{value}"),
+        JsonValue::Number(JsonNumber::PosInt(n)) => write!(f, "{n}"),
+        // jsep has no negative literals, `-5` parses back as a negation of `5`, so only
+        // trees that came out of the parser print to their own source
+        JsonValue::Number(JsonNumber::NegInt(n)) => write!(f, "{n}"),
+        // `inf` and `NaN` are not numbers to the parser either
+        JsonValue::Number(JsonNumber::Float(n)) if !n.is_finite() => Err(fmt::Error),
+        JsonValue::Number(JsonNumber::Float(n)) => write!(f, "{n}"),
+        JsonValue::String(value) => write_string(f, value),
+        // jsep literals are never arrays or objects, fall back to a JSON-ish form
+        other => write!(f, "{other:?}"),
+    }
+}
+
+impl fmt::Display for JsepNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsepNode::ConditionalExpression(node) => {
+                write_operand(f, &node.test, CONDITIONAL + 1)?;
+                write!(f, " ? {} : {}", node.consequent, node.alternate)
+            }
+            JsepNode::BinaryExpression(node) => {
+                let precedence = node.operator.precedence();
+                write_operand(f, &node.left, precedence)?;
+                write!(f, " {} ", node.operator)?;
+                write_operand(f, &node.right, precedence + 1)
+            }
+            JsepNode::UnaryExpression(node) => {
+                let mut argument = String::new();
+                write_operand(&mut argument, &node.argument, UNARY)?;
+                // keep `- -a` from turning into `--a`
+                let operator = node.operator.as_str();
+                if argument.starts_with(operator) && operator != "!" {
+                    write!(f, "{operator} {argument}")
+                } else {
+                    write!(f, "{operator}{argument}")
+                }
+            }
+            JsepNode::CallExpression(node) => {
+                write_operand(f, &node.callee, POSTFIX)?;
+                f.write_char('(')?;
+                for (i, argument) in node.arguments.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{argument}")?;
+                }
+                f.write_char(')')
+            }
+            JsepNode::MemberExpression(node) => {
+                write_operand(f, &node.object, POSTFIX)?;
+                match (node.computed, node.optional) {
+                    (true, true) => write!(f, "?.[{}]", node.property),
+                    (true, false) => write!(f, "[{}]", node.property),
+                    (false, true) => write!(f, "?.{}", node.property),
+                    (false, false) => write!(f, ".{}", node.property),
+                }
+            }
+            JsepNode::Identifier(node) => f.write_str(&node.name),
+            JsepNode::Literal(node) => write_literal(f, &node.value),
+        }
+    }
+}
@@ -10,7 +10,7 @@ impl AsRef<str> for JsonKey {
     }
 }
 
-#[derive(Archive, Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Archive, Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[archive(
     bound(
         serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::SharedSerializeRegistry + rkyv::ser::Serializer",
@@ -102,17 +102,11 @@ impl JsonValue {
     }
 
     pub fn is_null(&self) -> bool {
-        match self {
-            JsonValue::Null => true,
-            _ => false,
-        }
+        matches!(self, JsonValue::Null)
     }
 
     pub fn is_object(&self) -> bool {
-        match self {
-            JsonValue::Object(_) => true,
-            _ => false,
-        }
+        matches!(self, JsonValue::Object(_))
     }
 
     pub fn as_object(&self) -> Option<&HashMap<String, JsonValue>> {
@@ -123,10 +117,7 @@ impl JsonValue {
     }
 
     pub fn is_array(&self) -> bool {
-        match self {
-            JsonValue::Array(_) => true,
-            _ => false,
-        }
+        matches!(self, JsonValue::Array(_))
     }
 
     pub fn as_array(&self) -> Option<&[JsonValue]> {
@@ -159,30 +150,21 @@ impl JsonValue {
 
     pub fn as_f64(&self) -> Option<f64> {
         match self {
-            JsonValue::Number(n) => match n {
-                JsonNumber::Float(v) => Some(*v),
-                _ => None,
-            },
+            JsonValue::Number(JsonNumber::Float(v)) => Some(*v),
             _ => None,
         }
     }
 
     pub fn as_i64(&self) -> Option<i64> {
         match self {
-            JsonValue::Number(n) => match n {
-                JsonNumber::NegInt(v) => Some(*v),
-                _ => None,
-            },
+            JsonValue::Number(JsonNumber::NegInt(v)) => Some(*v),
             _ => None,
         }
     }
 
     pub fn as_u64(&self) -> Option<u64> {
         match self {
-            JsonValue::Number(n) => match n {
-                JsonNumber::PosInt(v) => Some(*v),
-                _ => None,
-            },
+            JsonValue::Number(JsonNumber::PosInt(v)) => Some(*v),
             _ => None,
         }
     }
@@ -193,6 +175,27 @@ impl JsonValue {
             _ => None,
         }
     }
+
+    pub fn get_path(&self, path: &str) -> Option<&JsonValue> {
+        path.split('.')
+            .try_fold(self, |value, key| value.as_object()?.get(key))
+    }
+
+    pub fn set_path(&mut self, path: &str, value: JsonValue) {
+        let mut current = self;
+        let mut keys = path.split('.').peekable();
+        while let Some(key) = keys.next() {
+            if !current.is_object() {
+                *current = JsonValue::map();
+            }
+            let obj = current.as_object_mut().unwrap();
+            if keys.peek().is_none() {
+                obj.insert(key.to_string(), value);
+                return;
+            }
+            current = obj.entry(key.to_string()).or_insert_with(JsonValue::map);
+        }
+    }
 }
 
 impl fmt::Display for ArchivedJsonValue {
@@ -227,7 +230,7 @@ impl fmt::Display for ArchivedJsonValue {
     }
 }
 
-#[derive(Archive, Debug, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[derive(Archive, Clone, Debug, Deserialize, Serialize, PartialEq, PartialOrd)]
 #[archive(check_bytes)]
 #[archive_attr(derive(Debug))]
 pub enum JsonNumber {
@@ -246,6 +249,19 @@ impl JsonNumber {
     }
 }
 
+impl From<f64> for JsonNumber {
+    fn from(value: f64) -> Self {
+        if value.is_finite() && value.fract() == 0.0 {
+            if value >= 0.0 && value <= u64::MAX as f64 {
+                return Self::PosInt(value as u64);
+            } else if value < 0.0 && value >= i64::MIN as f64 {
+                return Self::NegInt(value as i64);
+            }
+        }
+        Self::Float(value)
+    }
+}
+
 impl fmt::Display for ArchivedJsonNumber {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
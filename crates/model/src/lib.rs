@@ -7,9 +7,9 @@ pub mod json;
 mod model;
 pub use model::*;
 
-pub fn serialize(workflow: WorkflowDefinition) -> AlignedVec {
+pub fn serialize(workflow: &WorkflowDefinition) -> AlignedVec {
     let mut serializer = AllocSerializer::<0>::default();
-    serializer.serialize_value(&workflow).unwrap();
+    serializer.serialize_value(workflow).unwrap();
     serializer.into_serializer().into_inner()
 }
 
@@ -19,3 +19,16 @@ pub fn deserialize(
     let archived = unsafe { rkyv::archived_root::<WorkflowDefinition>(data) };
     archived.deserialize(&mut rkyv::de::deserializers::SharedDeserializeMap::default())
 }
+
+pub fn serialize_bundle(bundle: WorkflowBundle) -> AlignedVec {
+    let mut serializer = AllocSerializer::<0>::default();
+    serializer.serialize_value(&bundle).unwrap();
+    serializer.into_serializer().into_inner()
+}
+
+pub fn deserialize_bundle(
+    data: &[u8],
+) -> Result<WorkflowBundle, rkyv::de::deserializers::SharedDeserializeMapError> {
+    let archived = unsafe { rkyv::archived_root::<WorkflowBundle>(data) };
+    archived.deserialize(&mut rkyv::de::deserializers::SharedDeserializeMap::default())
+}
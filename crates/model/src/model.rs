@@ -1,6 +1,6 @@
 use crate::jsep::JsepNode;
 use rkyv::{Archive, Deserialize, Serialize};
-use std::sync::Arc;
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
 
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 #[archive(compare(PartialEq))]
@@ -22,6 +22,55 @@ pub struct EndEventDef {
 pub struct UserTaskDef {
     pub incoming: Arc<[i32]>,
     pub outgoing: Arc<[i32]>,
+    pub schema: Option<Arc<[VariableDef]>>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub enum VariableType {
+    Any,
+    Boolean,
+    Number,
+    String,
+    Object,
+    Array,
+}
+
+impl FromStr for VariableType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(VariableType::Any),
+            "boolean" => Ok(VariableType::Boolean),
+            "number" => Ok(VariableType::Number),
+            "string" => Ok(VariableType::String),
+            "object" => Ok(VariableType::Object),
+            "array" => Ok(VariableType::Array),
+            _ => Err(format!("Invalid variable type '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for VariableType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableType::Any => write!(f, "any"),
+            VariableType::Boolean => write!(f, "boolean"),
+            VariableType::Number => write!(f, "number"),
+            VariableType::String => write!(f, "string"),
+            VariableType::Object => write!(f, "object"),
+            VariableType::Array => write!(f, "array"),
+        }
+    }
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct VariableDef {
+    pub name: Arc<str>,
+    pub kind: VariableType,
 }
 
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
@@ -33,6 +82,59 @@ pub struct ExclusiveGatewayDef {
     pub default: i32,
 }
 
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct InclusiveGatewayDef {
+    pub incoming: Arc<[i32]>,
+    pub outgoing: Arc<[i32]>,
+    pub default: i32,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct ParallelGatewayDef {
+    pub incoming: Arc<[i32]>,
+    pub outgoing: Arc<[i32]>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct SubProcessDef {
+    pub incoming: Arc<[i32]>,
+    pub outgoing: Arc<[i32]>,
+    pub child: i32,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct PassThroughDef {
+    pub incoming: Arc<[i32]>,
+    pub outgoing: Arc<[i32]>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct VariableMapping {
+    pub source: Arc<str>,
+    pub target: Arc<str>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct CallActivityDef {
+    pub incoming: Arc<[i32]>,
+    pub outgoing: Arc<[i32]>,
+    pub called_element: Arc<str>,
+    pub inputs: Arc<[VariableMapping]>,
+    pub outputs: Arc<[VariableMapping]>,
+}
+
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 #[archive(compare(PartialEq))]
 #[archive_attr(derive(Debug))]
@@ -41,6 +143,11 @@ pub enum TaskDef {
     EndEvent(EndEventDef),
     UserTask(UserTaskDef),
     ExclusiveGateway(ExclusiveGatewayDef),
+    ParallelGateway(ParallelGatewayDef),
+    InclusiveGateway(InclusiveGatewayDef),
+    SubProcess(SubProcessDef),
+    CallActivity(CallActivityDef),
+    PassThrough(PassThroughDef),
 }
 
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
@@ -53,9 +160,51 @@ pub struct Task {
 
 impl Task {
     pub fn is_user_task(&self) -> bool {
+        matches!(&self.def, TaskDef::UserTask(_))
+    }
+
+    pub fn is_parallel_gateway(&self) -> bool {
+        matches!(&self.def, TaskDef::ParallelGateway(_))
+    }
+
+    pub fn is_inclusive_gateway(&self) -> bool {
+        matches!(&self.def, TaskDef::InclusiveGateway(_))
+    }
+
+    pub fn is_gateway(&self) -> bool {
+        matches!(
+            &self.def,
+            TaskDef::ExclusiveGateway(_)
+                | TaskDef::ParallelGateway(_)
+                | TaskDef::InclusiveGateway(_)
+        )
+    }
+
+    pub fn incoming(&self) -> &[i32] {
+        match &self.def {
+            TaskDef::StartEvent(_) => &[],
+            TaskDef::EndEvent(ev) => &ev.incoming,
+            TaskDef::UserTask(ev) => &ev.incoming,
+            TaskDef::ExclusiveGateway(ev) => &ev.incoming,
+            TaskDef::ParallelGateway(ev) => &ev.incoming,
+            TaskDef::InclusiveGateway(ev) => &ev.incoming,
+            TaskDef::SubProcess(ev) => &ev.incoming,
+            TaskDef::CallActivity(ev) => &ev.incoming,
+            TaskDef::PassThrough(ev) => &ev.incoming,
+        }
+    }
+
+    pub fn outgoing(&self) -> &[i32] {
         match &self.def {
-            TaskDef::UserTask(_) => true,
-            _ => false,
+            TaskDef::StartEvent(ev) => &ev.outgoing,
+            TaskDef::EndEvent(_) => &[],
+            TaskDef::UserTask(ev) => &ev.outgoing,
+            TaskDef::ExclusiveGateway(ev) => &ev.outgoing,
+            TaskDef::ParallelGateway(ev) => &ev.outgoing,
+            TaskDef::InclusiveGateway(ev) => &ev.outgoing,
+            TaskDef::SubProcess(ev) => &ev.outgoing,
+            TaskDef::CallActivity(ev) => &ev.outgoing,
+            TaskDef::PassThrough(ev) => &ev.outgoing,
         }
     }
 }
@@ -67,6 +216,14 @@ pub enum ConditionExpression {
     Jsep(JsepNode),
 }
 
+impl std::fmt::Display for ConditionExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConditionExpression::Jsep(node) => write!(f, "{node}"),
+        }
+    }
+}
+
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 #[archive(compare(PartialEq))]
 #[archive_attr(derive(Debug))]
@@ -84,6 +241,25 @@ pub struct WorkflowProperties {
     pub autostart: bool,
 }
 
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq, Clone, Copy)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub enum VersionSource {
+    Tag,
+    Supplied,
+    ContentHash,
+}
+
+impl std::fmt::Display for VersionSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionSource::Tag => write!(f, "tag"),
+            VersionSource::Supplied => write!(f, "supplied"),
+            VersionSource::ContentHash => write!(f, "content-hash"),
+        }
+    }
+}
+
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 #[archive(
     bound(
@@ -95,6 +271,7 @@ pub struct WorkflowProperties {
 #[archive_attr(derive(Debug))]
 pub struct WorkflowDefinition {
     pub version: Arc<str>,
+    pub version_source: VersionSource,
     pub id: Arc<str>,
     pub start_event: i32,
     #[omit_bounds]
@@ -117,6 +294,28 @@ impl WorkflowDefinition {
         }
     }
 
+    pub fn task_by_id(&self, id: &str) -> Option<&Task> {
+        let index = self
+            .task_ids
+            .iter()
+            .position(|task_id| task_id.as_ref() == id)?;
+        self.tasks.get(index)
+    }
+
+    pub fn sub_process(&self, task_id: i32) -> Option<&WorkflowDefinition> {
+        match self.tasks.get(task_id as usize).map(|task| &task.def) {
+            Some(TaskDef::SubProcess(ev)) => self
+                .children
+                .as_ref()
+                .and_then(|children| children.get(ev.child as usize)),
+            _ => None,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.id.as_ref(), self.version.as_ref())
+    }
+
     pub fn format_id(&self, id: &str) -> String {
         format!("{}_{id}", self.id.as_ref())
     }
@@ -135,3 +334,85 @@ impl WorkflowDefinition {
             .collect()
     }
 }
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct Participant {
+    pub id: Arc<str>,
+    pub name: Option<Arc<str>>,
+    pub process_ref: Option<Arc<str>>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct Lane {
+    pub id: Arc<str>,
+    pub name: Option<Arc<str>>,
+    pub process_ref: Arc<str>,
+    pub flow_node_refs: Arc<[Arc<str>]>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+#[archive(compare(PartialEq))]
+#[archive_attr(derive(Debug))]
+pub struct WorkflowBundle {
+    pub processes: Arc<[WorkflowDefinition]>,
+    pub participants: Arc<[Participant]>,
+    pub lanes: Arc<[Lane]>,
+    index: BTreeMap<Arc<str>, u32>,
+}
+
+impl WorkflowBundle {
+    pub fn new(
+        processes: Arc<[WorkflowDefinition]>,
+        participants: Arc<[Participant]>,
+        lanes: Arc<[Lane]>,
+    ) -> Self {
+        let index = processes
+            .iter()
+            .enumerate()
+            .map(|(position, definition)| (definition.id.clone(), position as u32))
+            .collect();
+        Self {
+            processes,
+            participants,
+            lanes,
+            index,
+        }
+    }
+
+    pub fn get(&self, process_id: &str) -> Option<&WorkflowDefinition> {
+        let position = *self.index.get(process_id)?;
+        self.processes.get(position as usize)
+    }
+
+    // the first process that has tasks, participant-only processes are never executed
+    pub fn root(&self) -> Option<&WorkflowDefinition> {
+        self.processes
+            .iter()
+            .find(|definition| !definition.tasks.is_empty())
+    }
+
+    pub fn participant(&self, process_id: &str) -> Option<&Participant> {
+        self.participants
+            .iter()
+            .find(|participant| participant.process_ref.as_deref() == Some(process_id))
+    }
+
+    pub fn lanes(&self, process_id: &str) -> impl Iterator<Item = &Lane> {
+        let process_id = process_id.to_string();
+        self.lanes
+            .iter()
+            .filter(move |lane| lane.process_ref.as_ref() == process_id)
+    }
+
+    pub fn lane(&self, process_id: &str, task_id: &str) -> Option<&Lane> {
+        self.lanes(process_id).find(|lane| {
+            lane.flow_node_refs
+                .iter()
+                .any(|node| node.as_ref() == task_id)
+        })
+    }
+}
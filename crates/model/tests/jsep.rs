@@ -0,0 +1,180 @@
+use serde_json::{json, Map, Value};
+use std::fmt::Write;
+use wfrs_model::jsep::{self, ExpressionLiteral, JsepNode};
+use wfrs_model::json::{JsonNumber, JsonValue};
+
+fn literal(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(value) => json!(value),
+        JsonValue::Number(value) => json!(value.as_f64()),
+        JsonValue::String(value) => json!(value),
+        other => panic!("unexpected literal {other:?}"),
+    }
+}
+
+// render a node the way jsep serializes it, minus `raw`
+fn to_jsep(node: &JsepNode) -> Value {
+    match node {
+        JsepNode::BinaryExpression(node) => json!({
+            "type": "BinaryExpression",
+            "operator": node.operator.as_str(),
+            "left": to_jsep(&node.left),
+            "right": to_jsep(&node.right),
+        }),
+        JsepNode::UnaryExpression(node) => json!({
+            "type": "UnaryExpression",
+            "operator": node.operator.as_str(),
+            "argument": to_jsep(&node.argument),
+            "prefix": node.prefix,
+        }),
+        JsepNode::ConditionalExpression(node) => json!({
+            "type": "ConditionalExpression",
+            "test": to_jsep(&node.test),
+            "consequent": to_jsep(&node.consequent),
+            "alternate": to_jsep(&node.alternate),
+        }),
+        JsepNode::CallExpression(node) => json!({
+            "type": "CallExpression",
+            "arguments": node.arguments.iter().map(to_jsep).collect::<Vec<_>>(),
+            "callee": to_jsep(&node.callee),
+        }),
+        JsepNode::MemberExpression(node) => {
+            let mut member = json!({
+                "type": "MemberExpression",
+                "computed": node.computed,
+                "object": to_jsep(&node.object),
+                "property": to_jsep(&node.property),
+            });
+            if node.optional {
+                member["optional"] = json!(true);
+            }
+            member
+        }
+        JsepNode::Identifier(node) => json!({"type": "Identifier", "name": node.name.as_ref()}),
+        JsepNode::Literal(node) => json!({"type": "Literal", "value": literal(&node.value)}),
+    }
+}
+
+// jsep numbers are doubles and `raw` is not kept by the model
+fn normalize(value: Value) -> Value {
+    match value {
+        Value::Number(number) => json!(number.as_f64()),
+        Value::Array(values) => Value::Array(values.into_iter().map(normalize).collect()),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(key, _)| key != "raw")
+                .map(|(key, value)| (key, normalize(value)))
+                .collect::<Map<_, _>>(),
+        ),
+        value => value,
+    }
+}
+
+#[test]
+fn golden() {
+    let cases: Vec<Value> = serde_json::from_str(include_str!("./jsep.json")).unwrap();
+    for case in cases {
+        let expression = case["expression"].as_str().unwrap();
+        match (jsep::parse(expression), case.get("ast")) {
+            (Ok(node), Some(ast)) => {
+                assert_eq!(to_jsep(&node), normalize(ast.clone()), "{expression}")
+            }
+            (Err(err), None) => {
+                assert_eq!(err.message, case["error"]["description"], "{expression}");
+                assert_eq!(err.index as u64, case["error"]["index"], "{expression}");
+            }
+            (result, _) => panic!("{expression}: unexpected {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn unsupported() {
+    let err = jsep::parse("a | b").unwrap_err();
+    assert_eq!(err.to_string(), "Unsupported operator '|' at character 2");
+    let err = jsep::parse("a && ~b").unwrap_err();
+    assert_eq!(err.index, 5);
+    assert!(jsep::parse("this.a").is_err());
+    assert!(jsep::parse("[1, 2]").is_err());
+    assert!(jsep::parse("a?.()").is_err());
+    assert!(jsep::parse("").is_err());
+}
+
+#[test]
+fn round_trip() {
+    let cases: Vec<Value> = serde_json::from_str(include_str!("./jsep.json")).unwrap();
+    let expressions = cases
+        .iter()
+        .filter(|case| case.get("ast").is_some())
+        .map(|case| case["expression"].as_str().unwrap());
+    let extra = [
+        "a - (b - c)",
+        "a / (b * c) % d",
+        "(a ? b : c) ? d : e",
+        "a ? b ? c : d : e",
+        "(a || b) && !(c == d)",
+        "-(a + b) * +c",
+        "- -a + - (-1)",
+        "!!a",
+        "(1).toString(\"x\\\"y\\\\\")",
+        "f(a ? b : c, d)",
+        "(a + b)[c]?.d(e)(f)",
+        "'tab\\there' + 2.5e-3",
+    ];
+    for expression in expressions.chain(extra) {
+        let node = jsep::parse(expression).unwrap();
+        let printed = node.to_string();
+        let reparsed = jsep::parse(&printed).unwrap_or_else(|err| panic!("{printed}: {err}"));
+        assert_eq!(node, reparsed, "{expression} printed as {printed}");
+    }
+}
+
+#[test]
+fn print() {
+    let cases = [
+        ("a + b * c", "a + b * c"),
+        ("(a + b) * c", "(a + b) * c"),
+        ("a - (b - c)", "a - (b - c)"),
+        ("((a - b)) - c", "a - b - c"),
+        ("(a ? b : c) ? d : e", "(a ? b : c) ? d : e"),
+        ("a ? (b ? c : d) : (e ? f : g)", "a ? b ? c : d : e ? f : g"),
+        ("!(a && b) || (c)", "!(a && b) || c"),
+        ("- -a", "- -a"),
+        ("-(a.b)", "-a.b"),
+        ("(-a).b", "(-a).b"),
+        ("(1).x", "(1).x"),
+        ("(a + b)[c]", "(a + b)[c]"),
+        ("a?.[0]?.b", "a?.[0]?.b"),
+        ("'it\\'s'", "\"it's\""),
+        ("true != null", "true != null"),
+    ];
+    for (source, expected) in cases {
+        let node = jsep::parse(source).unwrap();
+        assert_eq!(node.to_string(), expected, "{source}");
+    }
+}
+
+#[test]
+fn print_numbers() {
+    let number = |value| {
+        JsepNode::Literal(ExpressionLiteral {
+            value: JsonValue::Number(value),
+        })
+    };
+
+    // the parser reads `-5` as a negation, a hand built negative literal does not round-trip
+    let negative = number(JsonNumber::NegInt(-5));
+    assert_eq!(negative.to_string(), "-5");
+    let reparsed = jsep::parse(&negative.to_string()).unwrap();
+    assert!(matches!(reparsed, JsepNode::UnaryExpression(_)));
+    assert_ne!(reparsed, negative);
+    assert_eq!(reparsed.to_string(), "-5");
+
+    for value in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+        let mut printed = String::new();
+        assert!(write!(printed, "{}", number(JsonNumber::Float(value))).is_err());
+    }
+    let err = jsep::parse("1e999").unwrap_err();
+    assert_eq!(err.to_string(), "Invalid number (1e999) at character 5");
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+use thiserror::Error;
+use wfrs_validator::Diagnostic;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn at(offset: usize) -> Self {
+        Self {
+            offset,
+            ..Default::default()
+        }
+    }
+
+    // moves to the start tag of the first `name` element after this position
+    fn child(&mut self, xml: &str, name: &str) {
+        let rest = xml.get(self.offset..).unwrap_or_default();
+        if let Some(start) = rest.find(name).and_then(|end| rest[..end].rfind('<')) {
+            self.offset += start;
+        }
+    }
+
+    fn locate(&mut self, xml: &str) {
+        let offset = self.offset.min(xml.len());
+        let before = xml.get(..offset).unwrap_or(xml);
+        self.line = before.matches('\n').count() + 1;
+        self.column = before
+            .rsplit('\n')
+            .next()
+            .map(|line| line.chars().count() + 1)
+            .unwrap_or(1);
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum XmlError {
+    #[error("no process definition found in provided xml")]
+    NoProcessDefinition,
+    #[error("{position}: malformed xml: {source}")]
+    Read {
+        position: Position,
+        source: Box<quick_xml::Error>,
+    },
+    #[error("{position}: unexpected end of file inside <{element}>")]
+    UnexpectedEof { position: Position, element: String },
+    #[error("{position}: unable to read <{element}>: {source}")]
+    Deserialize {
+        position: Position,
+        element: String,
+        id: Option<String>,
+        source: Box<quick_xml::DeError>,
+    },
+    #[error("{position}: <{element}> is missing required attribute '{attribute}'")]
+    MissingAttribute {
+        position: Position,
+        element: String,
+        id: Option<String>,
+        attribute: String,
+    },
+    #[error("{position}: <{element}> is not supported")]
+    UnsupportedElement {
+        position: Position,
+        element: String,
+        id: Option<String>,
+    },
+    #[error("{position}: invalid condition expression on '{id}' at character {index}: {message}")]
+    InvalidExpression {
+        position: Position,
+        element: String,
+        id: String,
+        index: usize,
+        message: String,
+    },
+}
+
+impl XmlError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NoProcessDefinition => "NoProcessDefinition",
+            Self::Read { .. } => "Read",
+            Self::UnexpectedEof { .. } => "UnexpectedEof",
+            Self::Deserialize { .. } => "Deserialize",
+            Self::MissingAttribute { .. } => "MissingAttribute",
+            Self::UnsupportedElement { .. } => "UnsupportedElement",
+            Self::InvalidExpression { .. } => "InvalidExpression",
+        }
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Self::Read { position, .. }
+            | Self::UnexpectedEof { position, .. }
+            | Self::Deserialize { position, .. }
+            | Self::MissingAttribute { position, .. }
+            | Self::UnsupportedElement { position, .. }
+            | Self::InvalidExpression { position, .. } => Some(*position),
+            Self::NoProcessDefinition => None,
+        }
+    }
+
+    pub fn element(&self) -> Option<&str> {
+        match self {
+            Self::UnexpectedEof { element, .. }
+            | Self::Deserialize { element, .. }
+            | Self::MissingAttribute { element, .. }
+            | Self::UnsupportedElement { element, .. }
+            | Self::InvalidExpression { element, .. } => Some(element),
+            Self::NoProcessDefinition | Self::Read { .. } => None,
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Self::Deserialize { id, .. }
+            | Self::MissingAttribute { id, .. }
+            | Self::UnsupportedElement { id, .. } => id.as_deref(),
+            Self::InvalidExpression { id, .. } => Some(id),
+            Self::NoProcessDefinition | Self::Read { .. } | Self::UnexpectedEof { .. } => None,
+        }
+    }
+
+    pub fn locate(mut self, xml: &str) -> Self {
+        match &mut self {
+            Self::Read { position, .. }
+            | Self::UnexpectedEof { position, .. }
+            | Self::Deserialize { position, .. }
+            | Self::MissingAttribute { position, .. }
+            | Self::UnsupportedElement { position, .. } => position.locate(xml),
+            Self::InvalidExpression { position, .. } => {
+                position.child(xml, "conditionExpression");
+                position.locate(xml);
+            }
+            Self::NoProcessDefinition => (),
+        }
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseWarning {
+    pub position: Position,
+    pub element: String,
+    pub id: Option<String>,
+    pub pass_through: bool,
+}
+
+impl ParseWarning {
+    pub fn kind(&self) -> &'static str {
+        if self.pass_through {
+            "PassThroughElement"
+        } else {
+            "SkippedElement"
+        }
+    }
+
+    pub fn locate(mut self, xml: &str) -> Self {
+        self.position.locate(xml);
+        self
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = if self.pass_through {
+            "treated as a pass-through task"
+        } else {
+            "skipped"
+        };
+        write!(
+            f,
+            "{}: <{}> is not supported, {action}",
+            self.position, self.element
+        )
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseIssue {
+    pub severity: String,
+    pub kind: String,
+    pub message: String,
+    pub element: Option<String>,
+    pub id: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl From<&XmlError> for ParseIssue {
+    fn from(err: &XmlError) -> Self {
+        let position = err.position();
+        Self {
+            severity: "error".to_string(),
+            kind: err.kind().to_string(),
+            message: err.to_string(),
+            element: err.element().map(ToString::to_string),
+            id: err.id().map(ToString::to_string),
+            line: position.map(|position| position.line),
+            column: position.map(|position| position.column),
+        }
+    }
+}
+
+impl From<&Diagnostic> for ParseIssue {
+    fn from(diagnostic: &Diagnostic) -> Self {
+        Self {
+            severity: diagnostic.severity.to_string(),
+            kind: format!("{:?}", diagnostic.kind),
+            message: diagnostic.message.clone(),
+            element: None,
+            id: Some(diagnostic.element.to_string()),
+            line: None,
+            column: None,
+        }
+    }
+}
+
+impl From<&ParseWarning> for ParseIssue {
+    fn from(warning: &ParseWarning) -> Self {
+        Self {
+            severity: "warning".to_string(),
+            kind: warning.kind().to_string(),
+            message: warning.to_string(),
+            element: Some(warning.element.clone()),
+            id: warning.id.clone(),
+            line: Some(warning.position.line),
+            column: Some(warning.position.column),
+        }
+    }
+}
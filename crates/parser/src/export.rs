@@ -0,0 +1,466 @@
+use quick_xml::escape::escape;
+use wfrs_model::{TaskDef, VersionSource, WorkflowDefinition};
+
+const SHAPE_SPACING_X: i32 = 170;
+const SHAPE_SPACING_Y: i32 = 130;
+const ORIGIN_X: i32 = 180;
+const ORIGIN_Y: i32 = 200;
+
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    pub layout: bool,
+}
+
+struct XmlWriter {
+    out: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn new() -> Self {
+        Self {
+            out: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            depth: 0,
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.out.push_str(&"  ".repeat(self.depth));
+        self.out.push('<');
+        self.out.push_str(name);
+        for (key, value) in attributes {
+            self.out.push_str(&format!(" {key}=\"{}\"", escape(value)));
+        }
+    }
+
+    fn open(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.start_tag(name, attributes);
+        self.out.push_str(">\n");
+        self.depth += 1;
+    }
+
+    fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.start_tag(name, attributes);
+        self.out.push_str(" />\n");
+    }
+
+    fn text(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) {
+        self.start_tag(name, attributes);
+        self.out.push_str(&format!(">{}</{name}>\n", escape(text)));
+    }
+
+    fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        self.out.push_str(&"  ".repeat(self.depth));
+        self.out.push_str(&format!("</{name}>\n"));
+    }
+}
+
+fn task_id(definition: &WorkflowDefinition, index: i32) -> &str {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| definition.task_ids.get(index))
+        .map(AsRef::as_ref)
+        .unwrap_or_default()
+}
+
+fn flow_id(definition: &WorkflowDefinition, index: i32) -> &str {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| definition.flow_ids.get(index))
+        .map(AsRef::as_ref)
+        .unwrap_or_default()
+}
+
+fn write_connections(
+    w: &mut XmlWriter,
+    definition: &WorkflowDefinition,
+    incoming: &[i32],
+    outgoing: &[i32],
+) {
+    for flow in incoming {
+        w.text("bpmn:incoming", &[], flow_id(definition, *flow));
+    }
+    for flow in outgoing {
+        w.text("bpmn:outgoing", &[], flow_id(definition, *flow));
+    }
+}
+
+// the parser falls back to the first outgoing flow, only spell out a default
+// that differs from that or that the modeler would render as a default path
+fn gateway_default<'d>(
+    definition: &'d WorkflowDefinition,
+    outgoing: &[i32],
+    default: i32,
+) -> Option<&'d str> {
+    let flow = definition.flows.get(usize::try_from(default).ok()?)?;
+    if outgoing.first() == Some(&default) && flow.condition_expression.is_some() {
+        return None;
+    }
+    Some(flow_id(definition, default))
+}
+
+fn write_flow_elements(w: &mut XmlWriter, definition: &WorkflowDefinition) {
+    for (task, id) in definition.tasks.iter().zip(definition.task_ids.iter()) {
+        let id = id.as_ref();
+        match &task.def {
+            TaskDef::StartEvent(e) => {
+                w.open("bpmn:startEvent", &[("id", id)]);
+                write_connections(w, definition, &[], &e.outgoing);
+                w.close("bpmn:startEvent");
+            }
+            TaskDef::EndEvent(e) => {
+                w.open("bpmn:endEvent", &[("id", id)]);
+                write_connections(w, definition, &e.incoming, &[]);
+                w.close("bpmn:endEvent");
+            }
+            TaskDef::UserTask(e) => {
+                w.open("bpmn:userTask", &[("id", id)]);
+                if let Some(schema) = e.schema.as_ref() {
+                    w.open("bpmn:extensionElements", &[]);
+                    w.open("camunda:properties", &[]);
+                    for variable in schema.iter() {
+                        let name = format!("var:{}", variable.name);
+                        let kind = variable.kind.to_string();
+                        w.empty("camunda:property", &[("name", &name), ("value", &kind)]);
+                    }
+                    w.close("camunda:properties");
+                    w.close("bpmn:extensionElements");
+                }
+                write_connections(w, definition, &e.incoming, &e.outgoing);
+                w.close("bpmn:userTask");
+            }
+            TaskDef::ExclusiveGateway(e) => {
+                let mut attributes = vec![("id", id)];
+                attributes.extend(
+                    gateway_default(definition, &e.outgoing, e.default).map(|f| ("default", f)),
+                );
+                w.open("bpmn:exclusiveGateway", &attributes);
+                write_connections(w, definition, &e.incoming, &e.outgoing);
+                w.close("bpmn:exclusiveGateway");
+            }
+            TaskDef::InclusiveGateway(e) => {
+                let mut attributes = vec![("id", id)];
+                attributes.extend(
+                    gateway_default(definition, &e.outgoing, e.default).map(|f| ("default", f)),
+                );
+                w.open("bpmn:inclusiveGateway", &attributes);
+                write_connections(w, definition, &e.incoming, &e.outgoing);
+                w.close("bpmn:inclusiveGateway");
+            }
+            TaskDef::ParallelGateway(e) => {
+                w.open("bpmn:parallelGateway", &[("id", id)]);
+                write_connections(w, definition, &e.incoming, &e.outgoing);
+                w.close("bpmn:parallelGateway");
+            }
+            TaskDef::SubProcess(e) => {
+                w.open("bpmn:subProcess", &[("id", id)]);
+                write_connections(w, definition, &e.incoming, &e.outgoing);
+                if let Some(child) = definition.sub_process(task.id) {
+                    write_flow_elements(w, child);
+                }
+                w.close("bpmn:subProcess");
+            }
+            TaskDef::CallActivity(e) => {
+                w.open(
+                    "bpmn:callActivity",
+                    &[("id", id), ("calledElement", &e.called_element)],
+                );
+                if !e.inputs.is_empty() || !e.outputs.is_empty() {
+                    w.open("bpmn:extensionElements", &[]);
+                    for mapping in e.inputs.iter() {
+                        w.empty(
+                            "camunda:in",
+                            &[("source", &mapping.source), ("target", &mapping.target)],
+                        );
+                    }
+                    for mapping in e.outputs.iter() {
+                        w.empty(
+                            "camunda:out",
+                            &[("source", &mapping.source), ("target", &mapping.target)],
+                        );
+                    }
+                    w.close("bpmn:extensionElements");
+                }
+                write_connections(w, definition, &e.incoming, &e.outgoing);
+                w.close("bpmn:callActivity");
+            }
+            // pass-throughs come from elements the engine does not know, a plain
+            // task keeps the shape in the modeler and is skipped again in lenient mode
+            TaskDef::PassThrough(e) => {
+                w.open("bpmn:task", &[("id", id)]);
+                write_connections(w, definition, &e.incoming, &e.outgoing);
+                w.close("bpmn:task");
+            }
+        }
+    }
+    for (flow, id) in definition.flows.iter().zip(definition.flow_ids.iter()) {
+        let attributes = [
+            ("id", id.as_ref()),
+            ("sourceRef", task_id(definition, flow.source_ref)),
+            ("targetRef", task_id(definition, flow.target_ref)),
+        ];
+        match flow.condition_expression.as_ref() {
+            Some(condition) => {
+                w.open("bpmn:sequenceFlow", &attributes);
+                w.text(
+                    "bpmn:conditionExpression",
+                    &[("xsi:type", "bpmn:tFormalExpression"), ("language", "jsep")],
+                    &condition.to_string(),
+                );
+                w.close("bpmn:sequenceFlow");
+            }
+            None => w.empty("bpmn:sequenceFlow", &attributes),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Bounds {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Bounds {
+    fn center_y(&self) -> i32 {
+        self.y + self.height / 2
+    }
+
+    fn center_x(&self) -> i32 {
+        self.x + self.width / 2
+    }
+
+    fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+}
+
+fn shape_size(def: &TaskDef) -> (i32, i32) {
+    match def {
+        TaskDef::StartEvent(_) | TaskDef::EndEvent(_) => (36, 36),
+        TaskDef::ExclusiveGateway(_)
+        | TaskDef::InclusiveGateway(_)
+        | TaskDef::ParallelGateway(_) => (50, 50),
+        _ => (100, 80),
+    }
+}
+
+// layered layout: columns follow the longest path from the start event with
+// loops broken at their back edge, rows fill up in visiting order
+fn layout(definition: &WorkflowDefinition) -> (Vec<Bounds>, Vec<bool>) {
+    let count = definition.tasks.len();
+    let target = |flow: &wfrs_model::Flow| usize::try_from(flow.target_ref).ok();
+    let source = |flow: &wfrs_model::Flow| usize::try_from(flow.source_ref).ok();
+    let mut back_edges = vec![false; definition.flows.len()];
+    let mut state = vec![0u8; count];
+    let mut post_order = Vec::with_capacity(count);
+    let roots = usize::try_from(definition.start_event)
+        .ok()
+        .into_iter()
+        .chain(0..count);
+    for root in roots.filter(|root| *root < count) {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0usize)];
+        state[root] = 1;
+        while let Some((node, next)) = stack.pop() {
+            let outgoing = definition
+                .flows
+                .iter()
+                .enumerate()
+                .filter(|(_, flow)| source(flow) == Some(node))
+                .nth(next);
+            match outgoing {
+                Some((index, flow)) => {
+                    stack.push((node, next + 1));
+                    match target(flow).filter(|target| *target < count) {
+                        Some(target) if state[target] == 0 => {
+                            state[target] = 1;
+                            stack.push((target, 0));
+                        }
+                        Some(target) if state[target] == 1 => back_edges[index] = true,
+                        _ => {}
+                    }
+                }
+                None => {
+                    state[node] = 2;
+                    post_order.push(node);
+                }
+            }
+        }
+    }
+    let mut columns = vec![0i32; count];
+    for node in post_order.iter().rev() {
+        for (index, flow) in definition.flows.iter().enumerate() {
+            if back_edges[index] || source(flow) != Some(*node) {
+                continue;
+            }
+            if let Some(target) = target(flow).filter(|target| *target < count) {
+                columns[target] = columns[target].max(columns[*node] + 1);
+            }
+        }
+    }
+    let mut rows = vec![0i32; count];
+    let mut bounds = vec![
+        Bounds {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+        count
+    ];
+    for node in post_order.iter().rev() {
+        let column = columns[*node];
+        let row = rows[column as usize];
+        rows[column as usize] += 1;
+        let (width, height) = shape_size(&definition.tasks[*node].def);
+        bounds[*node] = Bounds {
+            x: ORIGIN_X + column * SHAPE_SPACING_X - width / 2,
+            y: ORIGIN_Y + row * SHAPE_SPACING_Y - height / 2,
+            width,
+            height,
+        };
+    }
+    (bounds, back_edges)
+}
+
+fn waypoints(source: Bounds, target: Bounds, back_edge: bool) -> Vec<(i32, i32)> {
+    if back_edge {
+        let below = source.bottom().max(target.bottom()) + 40;
+        return vec![
+            (source.center_x(), source.bottom()),
+            (source.center_x(), below),
+            (target.center_x(), below),
+            (target.center_x(), target.bottom()),
+        ];
+    }
+    let (start, end) = (
+        (source.right(), source.center_y()),
+        (target.x, target.center_y()),
+    );
+    if start.1 == end.1 {
+        return vec![start, end];
+    }
+    let middle = (start.0 + end.0) / 2;
+    vec![start, (middle, start.1), (middle, end.1), end]
+}
+
+fn write_diagram(w: &mut XmlWriter, definition: &WorkflowDefinition) {
+    let (bounds, back_edges) = layout(definition);
+    let id = definition.id.as_ref();
+    w.open(
+        "bpmndi:BPMNDiagram",
+        &[("id", &format!("BPMNDiagram_{id}"))],
+    );
+    w.open(
+        "bpmndi:BPMNPlane",
+        &[("id", &format!("BPMNPlane_{id}")), ("bpmnElement", id)],
+    );
+    for ((task, id), bounds) in definition
+        .tasks
+        .iter()
+        .zip(definition.task_ids.iter())
+        .zip(bounds.iter())
+    {
+        let shape_id = format!("{id}_di");
+        let mut attributes = vec![("id", shape_id.as_str()), ("bpmnElement", id.as_ref())];
+        if let TaskDef::SubProcess(_) = task.def {
+            attributes.push(("isExpanded", "false"));
+        }
+        w.open("bpmndi:BPMNShape", &attributes);
+        w.empty(
+            "dc:Bounds",
+            &[
+                ("x", &bounds.x.to_string()),
+                ("y", &bounds.y.to_string()),
+                ("width", &bounds.width.to_string()),
+                ("height", &bounds.height.to_string()),
+            ],
+        );
+        w.close("bpmndi:BPMNShape");
+    }
+    for ((flow, id), back_edge) in definition
+        .flows
+        .iter()
+        .zip(definition.flow_ids.iter())
+        .zip(back_edges)
+    {
+        let source = usize::try_from(flow.source_ref)
+            .ok()
+            .and_then(|i| bounds.get(i));
+        let target = usize::try_from(flow.target_ref)
+            .ok()
+            .and_then(|i| bounds.get(i));
+        let (Some(source), Some(target)) = (source, target) else {
+            continue;
+        };
+        let edge_id = format!("{id}_di");
+        w.open(
+            "bpmndi:BPMNEdge",
+            &[("id", &edge_id), ("bpmnElement", id.as_ref())],
+        );
+        for (x, y) in waypoints(*source, *target, back_edge) {
+            w.empty(
+                "di:waypoint",
+                &[("x", &x.to_string()), ("y", &y.to_string())],
+            );
+        }
+        w.close("bpmndi:BPMNEdge");
+    }
+    w.close("bpmndi:BPMNPlane");
+    w.close("bpmndi:BPMNDiagram");
+    // collapsed sub-processes get a diagram of their own, like the modeler draws them
+    for task in definition.tasks.iter() {
+        if let Some(child) = definition.sub_process(task.id) {
+            write_diagram(w, child);
+        }
+    }
+}
+
+pub fn export(definition: &WorkflowDefinition, options: &ExportOptions) -> String {
+    let mut w = XmlWriter::new();
+    let id = definition.id.as_ref();
+    w.open(
+        "bpmn:definitions",
+        &[
+            ("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"),
+            ("xmlns:bpmn", "http://www.omg.org/spec/BPMN/20100524/MODEL"),
+            ("xmlns:bpmndi", "http://www.omg.org/spec/BPMN/20100524/DI"),
+            ("xmlns:dc", "http://www.omg.org/spec/DD/20100524/DC"),
+            ("xmlns:di", "http://www.omg.org/spec/DD/20100524/DI"),
+            ("xmlns:camunda", "http://camunda.org/schema/1.0/bpmn"),
+            ("id", &format!("Definitions_{id}")),
+            ("targetNamespace", "http://bpmn.io/schema/bpmn"),
+            ("exporter", "wfrs"),
+            ("exporterVersion", env!("CARGO_PKG_VERSION")),
+        ],
+    );
+    let mut attributes = vec![("id", id), ("isExecutable", "true")];
+    // only a tag survives a round trip, supplied versions and hashes are derived again
+    if definition.version_source == VersionSource::Tag {
+        attributes.push(("camunda:versionTag", definition.version.as_ref()));
+    }
+    w.open("bpmn:process", &attributes);
+    if definition.options.as_ref().is_some_and(|o| o.autostart) {
+        w.open("bpmn:extensionElements", &[]);
+        w.open("camunda:properties", &[]);
+        w.empty("camunda:property", &[("name", "autostart")]);
+        w.close("camunda:properties");
+        w.close("bpmn:extensionElements");
+    }
+    write_flow_elements(&mut w, definition);
+    w.close("bpmn:process");
+    if options.layout {
+        write_diagram(&mut w, definition);
+    }
+    w.close("bpmn:definitions");
+    w.out
+}
@@ -0,0 +1,1176 @@
+mod error;
+mod export;
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub use crate::error::{ParseIssue, ParseWarning, Position, XmlError};
+pub use crate::export::{export, ExportOptions};
+use quick_xml::de::Deserializer;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::{NsReader, Writer};
+use serde::de::DeserializeOwned;
+use wfrs_model::{
+    jsep, CallActivityDef, ConditionExpression, EndEventDef, ExclusiveGatewayDef, Flow,
+    InclusiveGatewayDef, ParallelGatewayDef, PassThroughDef, StartEventDef, SubProcessDef, Task,
+    TaskDef, UserTaskDef, VariableDef, VariableMapping, VariableType, VersionSource,
+    WorkflowBundle, WorkflowDefinition, WorkflowProperties,
+};
+use wfrs_validator::{check_conditions, diagnostic::has_errors, validate, Diagnostic};
+
+const BPMN_MODEL: &[u8] = b"http://www.omg.org/spec/BPMN/20100524/MODEL";
+const VENDOR_EXTENSIONS: [&[u8]; 2] = [
+    b"http://camunda.org/schema/1.0/bpmn",
+    b"http://camunda.org/schema/zeebe/1.0",
+];
+
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    pub lenient: bool,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Connection {
+    #[serde(rename = "$text")]
+    id: Arc<str>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StartEvent {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    outgoing: Arc<[Connection]>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct EndEvent {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    incoming: Arc<[Connection]>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserTask {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    extension_elements: Option<ExtensionElements>,
+    incoming: Arc<[Connection]>,
+    outgoing: Arc<[Connection]>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ExclusiveGateway {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "@default")]
+    default: Option<Arc<str>>,
+    incoming: Arc<[Connection]>,
+    outgoing: Arc<[Connection]>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct InclusiveGateway {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "@default")]
+    default: Option<Arc<str>>,
+    incoming: Arc<[Connection]>,
+    outgoing: Arc<[Connection]>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ParallelGateway {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    incoming: Arc<[Connection]>,
+    outgoing: Arc<[Connection]>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct VariableMap {
+    #[serde(rename = "@source")]
+    source: Option<Arc<str>>,
+    #[serde(rename = "@target")]
+    target: Option<Arc<str>>,
+}
+
+impl VariableMap {
+    fn to_mapping(&self) -> Option<VariableMapping> {
+        Some(VariableMapping {
+            source: self.source.clone()?,
+            target: self.target.clone()?,
+        })
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct CallActivityExtensions {
+    #[serde(rename = "in", default)]
+    inputs: Vec<VariableMap>,
+    #[serde(rename = "out", default)]
+    outputs: Vec<VariableMap>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallActivity {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "@calledElement")]
+    called_element: Arc<str>,
+    extension_elements: Option<CallActivityExtensions>,
+    incoming: Arc<[Connection]>,
+    outgoing: Arc<[Connection]>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct FlowNode {
+    #[serde(rename = "@id")]
+    id: Option<Arc<str>>,
+    #[serde(default)]
+    incoming: Vec<Connection>,
+    #[serde(default)]
+    outgoing: Vec<Connection>,
+}
+
+pub struct PassThrough {
+    id: Arc<str>,
+    incoming: Arc<[Connection]>,
+    outgoing: Arc<[Connection]>,
+}
+
+pub struct SubProcess {
+    id: Arc<str>,
+    incoming: Arc<[Connection]>,
+    outgoing: Arc<[Connection]>,
+    events: Vec<BpmnEvent>,
+    child: i32,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct BpmnExpression {
+    #[serde(rename = "@language")]
+    language: Arc<str>,
+    #[serde(rename = "$text")]
+    expr: Arc<str>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SequenceFlow {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "@sourceRef")]
+    source_ref: Arc<str>,
+    #[serde(rename = "@targetRef")]
+    target_ref: Arc<str>,
+    condition_expression: Option<BpmnExpression>,
+    #[serde(skip)]
+    position: Position,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Text {
+    #[serde(rename = "$text")]
+    _content: Arc<str>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TextAnnotation {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "text")]
+    _text: Text,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Association {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "@sourceRef")]
+    _source_ref: Arc<str>,
+    #[serde(rename = "@targetRef")]
+    _target_ref: Arc<str>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Property {
+    #[serde(rename = "@name")]
+    name: Option<Arc<str>>,
+    #[serde(rename = "@value")]
+    value: Option<Arc<str>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Properties {
+    property: Arc<[Property]>,
+}
+
+impl Properties {
+    fn has_flag(&self, name: &str) -> bool {
+        self.property
+            .iter()
+            .any(|p| p.name.as_deref() == Some(name))
+    }
+
+    fn schema(&self) -> Option<Arc<[VariableDef]>> {
+        let schema: Vec<VariableDef> = self
+            .property
+            .iter()
+            .filter_map(|p| {
+                let name = p.name.as_deref()?.strip_prefix("var:")?;
+                let kind = match p.value.as_deref().map(VariableType::from_str) {
+                    Some(Ok(kind)) => kind,
+                    Some(Err(err)) => {
+                        log::warn!("{err} for variable '{name}', falling back to 'any'");
+                        VariableType::Any
+                    }
+                    None => VariableType::Any,
+                };
+                Some(VariableDef {
+                    name: Arc::from(name),
+                    kind,
+                })
+            })
+            .collect();
+        if schema.is_empty() {
+            None
+        } else {
+            Some(Arc::from(schema))
+        }
+    }
+}
+
+impl From<Properties> for WorkflowProperties {
+    fn from(val: Properties) -> Self {
+        WorkflowProperties {
+            autostart: val.has_flag("autostart"),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct VersionTag {
+    #[serde(rename = "@value")]
+    value: Arc<str>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionElements {
+    properties: Option<Properties>,
+    version_tag: Option<VersionTag>,
+}
+
+pub enum BpmnEvent {
+    StartEvent(StartEvent),
+    EndEvent(EndEvent),
+    UserTask(UserTask),
+    ExclusiveGateway(ExclusiveGateway),
+    ParallelGateway(ParallelGateway),
+    InclusiveGateway(InclusiveGateway),
+    SubProcess(SubProcess),
+    CallActivity(CallActivity),
+    PassThrough(PassThrough),
+    SequenceFlow(SequenceFlow),
+    TextAnnotation(TextAnnotation),
+    Association(Association),
+}
+
+impl BpmnEvent {
+    pub fn id(&self) -> Arc<str> {
+        match self {
+            BpmnEvent::StartEvent(e) => e.id.clone(),
+            BpmnEvent::EndEvent(e) => e.id.clone(),
+            BpmnEvent::UserTask(e) => e.id.clone(),
+            BpmnEvent::ExclusiveGateway(e) => e.id.clone(),
+            BpmnEvent::ParallelGateway(e) => e.id.clone(),
+            BpmnEvent::InclusiveGateway(e) => e.id.clone(),
+            BpmnEvent::SubProcess(e) => e.id.clone(),
+            BpmnEvent::CallActivity(e) => e.id.clone(),
+            BpmnEvent::PassThrough(e) => e.id.clone(),
+            BpmnEvent::SequenceFlow(e) => e.id.clone(),
+            BpmnEvent::TextAnnotation(e) => e.id.clone(),
+            BpmnEvent::Association(e) => e.id.clone(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lane {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "@name")]
+    name: Option<Arc<str>>,
+    #[serde(default)]
+    flow_node_ref: Vec<Connection>,
+    child_lane_set: Option<LaneSet>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct LaneSet {
+    #[serde(default)]
+    lane: Vec<Lane>,
+}
+
+impl LaneSet {
+    fn collect_lanes(self, process_ref: &Arc<str>, lanes: &mut Vec<wfrs_model::Lane>) {
+        for lane in self.lane {
+            lanes.push(wfrs_model::Lane {
+                id: lane.id,
+                name: lane.name,
+                process_ref: process_ref.clone(),
+                flow_node_refs: lane.flow_node_ref.into_iter().map(|node| node.id).collect(),
+            });
+            if let Some(child) = lane.child_lane_set {
+                child.collect_lanes(process_ref, lanes);
+            }
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Participant {
+    #[serde(rename = "@id")]
+    id: Arc<str>,
+    #[serde(rename = "@name")]
+    name: Option<Arc<str>>,
+    #[serde(rename = "@processRef")]
+    process_ref: Option<Arc<str>>,
+}
+
+impl From<Participant> for wfrs_model::Participant {
+    fn from(val: Participant) -> Self {
+        wfrs_model::Participant {
+            id: val.id,
+            name: val.name,
+            process_ref: val.process_ref,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct Collaboration {
+    #[serde(default)]
+    participant: Vec<Participant>,
+}
+
+pub struct ProcessDefinition {
+    id: String,
+    version: String,
+    version_source: VersionSource,
+    events: Vec<BpmnEvent>,
+    options: Option<WorkflowProperties>,
+    lanes: Vec<wfrs_model::Lane>,
+}
+
+#[derive(Default)]
+pub struct Definitions {
+    processes: Vec<ProcessDefinition>,
+    participants: Vec<Participant>,
+}
+
+fn element_name(start_tag: &BytesStart) -> String {
+    String::from_utf8_lossy(start_tag.local_name().as_ref()).to_string()
+}
+
+fn element_id(start_tag: &BytesStart) -> Option<String> {
+    let attribute = start_tag.try_get_attribute("id").ok()??;
+    Some(attribute.unescape_value().ok()?.to_string())
+}
+
+fn bpmn_element<'e, R>(reader: &NsReader<R>, start_tag: &'e BytesStart) -> Option<&'e str> {
+    match reader.resolve_element(start_tag.name()) {
+        (ResolveResult::Bound(Namespace(BPMN_MODEL)), name) => {
+            std::str::from_utf8(name.into_inner()).ok()
+        }
+        _ => None,
+    }
+}
+
+fn extension_attribute<R>(
+    reader: &NsReader<R>,
+    start_tag: &BytesStart,
+    attribute: &str,
+) -> Result<Option<String>, XmlError> {
+    let read_error = |source: quick_xml::Error| XmlError::Read {
+        position: start_position(reader, start_tag),
+        source: Box::new(source),
+    };
+    for value in start_tag.attributes() {
+        let value = value.map_err(|err| read_error(err.into()))?;
+        let (namespace, name) = reader.resolve_attribute(value.key);
+        let vendor = match namespace {
+            ResolveResult::Bound(Namespace(namespace)) => VENDOR_EXTENSIONS.contains(&namespace),
+            _ => false,
+        };
+        if vendor && name.as_ref() == attribute.as_bytes() {
+            return Ok(Some(
+                value.unescape_value().map_err(read_error)?.to_string(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+fn start_position<R>(reader: &NsReader<R>, start_tag: &BytesStart) -> Position {
+    Position::at(reader.buffer_position().saturating_sub(start_tag.len() + 2))
+}
+
+fn read_event<'b, R: BufRead>(
+    reader: &mut NsReader<R>,
+    buf: &'b mut Vec<u8>,
+) -> Result<Event<'b>, XmlError> {
+    reader
+        .read_event_into(buf)
+        .map_err(|source| XmlError::Read {
+            position: Position::at(reader.buffer_position()),
+            source: Box::new(source),
+        })
+}
+
+fn required_attribute<R>(
+    reader: &NsReader<R>,
+    start_tag: &BytesStart,
+    attribute: &str,
+) -> Result<String, XmlError> {
+    let read_error = |source| XmlError::Read {
+        position: start_position(reader, start_tag),
+        source: Box::new(source),
+    };
+    match start_tag.try_get_attribute(attribute).map_err(read_error)? {
+        Some(value) => Ok(value.unescape_value().map_err(read_error)?.to_string()),
+        None => Err(XmlError::MissingAttribute {
+            position: start_position(reader, start_tag),
+            element: element_name(start_tag),
+            id: element_id(start_tag),
+            attribute: attribute.to_string(),
+        }),
+    }
+}
+
+fn read_to_end_into_buffer<R: BufRead>(
+    reader: &mut NsReader<R>,
+    start_tag: &BytesStart,
+    junk_buf: &mut Vec<u8>,
+) -> Result<Vec<u8>, XmlError> {
+    let mut depth = 0;
+    let mut output_buf: Vec<u8> = Vec::new();
+    let mut w = Writer::new(&mut output_buf);
+    let tag_name = start_tag.name();
+    let position = start_position(reader, start_tag);
+    let write_error = |source| XmlError::Read {
+        position,
+        source: Box::new(source),
+    };
+    w.write_event(Event::Start(start_tag.clone()))
+        .map_err(write_error)?;
+    loop {
+        junk_buf.clear();
+        let event = read_event(reader, junk_buf)?;
+        w.write_event(&event).map_err(write_error)?;
+
+        match event {
+            Event::Start(e) if e.name() == tag_name => depth += 1,
+            Event::End(e) if e.name() == tag_name => {
+                if depth == 0 {
+                    return Ok(output_buf);
+                }
+                depth -= 1;
+            }
+            Event::Eof => {
+                return Err(XmlError::UnexpectedEof {
+                    position: Position::at(reader.buffer_position()),
+                    element: element_name(start_tag),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn read_element<T, R: BufRead>(
+    reader: &mut NsReader<R>,
+    start_tag: &BytesStart,
+    junk_buf: &mut Vec<u8>,
+) -> Result<T, XmlError>
+where
+    T: DeserializeOwned,
+{
+    let position = start_position(reader, start_tag);
+    let release_bytes = read_to_end_into_buffer(reader, start_tag, junk_buf)?;
+    let str = std::str::from_utf8(&release_bytes).map_err(|err| XmlError::Read {
+        position,
+        source: Box::new(quick_xml::Error::NonDecodable(Some(err))),
+    })?;
+    // deserialize from buffer
+    let mut deserializer = Deserializer::from_str(str);
+    T::deserialize(&mut deserializer).map_err(|source| XmlError::Deserialize {
+        position,
+        element: element_name(start_tag),
+        id: element_id(start_tag),
+        source: Box::new(source),
+    })
+}
+
+#[derive(Default)]
+struct ParseContext {
+    lenient: bool,
+    version: Option<String>,
+    warnings: Vec<ParseWarning>,
+}
+
+impl ParseContext {
+    fn new(options: &ParseOptions) -> Self {
+        Self {
+            lenient: options.lenient,
+            version: options.version.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+fn content_hash(content: &str) -> String {
+    let hash = content
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
+
+fn read_process_definition<R: BufRead>(
+    reader: &mut NsReader<R>,
+    start_tag: &BytesStart,
+    buf: &mut Vec<u8>,
+    xml: &str,
+    context: &mut ParseContext,
+) -> Result<ProcessDefinition, XmlError> {
+    let id = required_attribute(reader, start_tag, "id")?;
+    let position = start_position(reader, start_tag);
+    let version_attribute = extension_attribute(reader, start_tag, "versionTag")?;
+    let FlowElements {
+        events,
+        options,
+        version_tag,
+        lane_sets,
+        ..
+    } = read_flow_elements(reader, start_tag, buf, context)?;
+    let (version, version_source) = match (version_attribute.or(version_tag), &context.version) {
+        (Some(tag), _) => (tag, VersionSource::Tag),
+        (None, Some(version)) => (version.clone(), VersionSource::Supplied),
+        (None, None) => {
+            let content = xml
+                .get(position.offset..reader.buffer_position())
+                .unwrap_or(xml);
+            (content_hash(content), VersionSource::ContentHash)
+        }
+    };
+    let process_ref = Arc::from(id.as_str());
+    let mut lanes = Vec::new();
+    for lane_set in lane_sets {
+        lane_set.collect_lanes(&process_ref, &mut lanes);
+    }
+    Ok(ProcessDefinition {
+        id,
+        version,
+        version_source,
+        events,
+        options,
+        lanes,
+    })
+}
+
+fn read_sub_process<R: BufRead>(
+    reader: &mut NsReader<R>,
+    start_tag: &BytesStart,
+    buf: &mut Vec<u8>,
+    context: &mut ParseContext,
+) -> Result<SubProcess, XmlError> {
+    let id = required_attribute(reader, start_tag, "id")?;
+    let FlowElements {
+        events,
+        incoming,
+        outgoing,
+        ..
+    } = read_flow_elements(reader, start_tag, buf, context)?;
+    Ok(SubProcess {
+        id: Arc::from(id.as_ref()),
+        incoming: Arc::from(incoming),
+        outgoing: Arc::from(outgoing),
+        events,
+        child: -1,
+    })
+}
+
+#[derive(Default)]
+struct FlowElements {
+    events: Vec<BpmnEvent>,
+    incoming: Vec<Connection>,
+    outgoing: Vec<Connection>,
+    options: Option<WorkflowProperties>,
+    version_tag: Option<String>,
+    lane_sets: Vec<LaneSet>,
+}
+
+fn read_unsupported_element<R: BufRead>(
+    reader: &mut NsReader<R>,
+    start_tag: &BytesStart,
+    junk_buf: &mut Vec<u8>,
+    context: &mut ParseContext,
+) -> Result<Option<BpmnEvent>, XmlError> {
+    let position = start_position(reader, start_tag);
+    let FlowNode {
+        id,
+        incoming,
+        outgoing,
+    } = read_element(reader, start_tag, junk_buf)?;
+    // a pass-through starts a token on every outgoing flow and never joins, so only
+    // elements on a single path can stand in for what they would have done
+    let element = element_name(start_tag);
+    if element.ends_with("Gateway") || incoming.len() > 1 || outgoing.len() > 1 {
+        return Err(XmlError::UnsupportedElement {
+            position,
+            element,
+            id: element_id(start_tag),
+        });
+    }
+    let pass_through = match id {
+        Some(id) if !incoming.is_empty() || !outgoing.is_empty() => Some(PassThrough {
+            id,
+            incoming: Arc::from(incoming),
+            outgoing: Arc::from(outgoing),
+        }),
+        _ => None,
+    };
+    context.warnings.push(ParseWarning {
+        position,
+        element,
+        id: element_id(start_tag),
+        pass_through: pass_through.is_some(),
+    });
+    Ok(pass_through.map(BpmnEvent::PassThrough))
+}
+
+fn read_flow_elements<R: BufRead>(
+    reader: &mut NsReader<R>,
+    start_tag: &BytesStart,
+    buf: &mut Vec<u8>,
+    context: &mut ParseContext,
+) -> Result<FlowElements, XmlError> {
+    let mut options = None;
+    let mut version_tag = None;
+    let mut lane_sets = Vec::new();
+    let tag_name = start_tag.name();
+    let mut junk_buf: Vec<u8> = Vec::new();
+    let mut events = Vec::new();
+    let mut incoming = Vec::new();
+    let mut outgoing = Vec::new();
+    loop {
+        junk_buf.clear();
+        let event = read_event(reader, buf)?;
+        match event {
+            Event::Start(e) => {
+                if let Some(name) = bpmn_element(reader, &e) {
+                    match name {
+                        "extensionElements" => {
+                            let extensions: ExtensionElements =
+                                read_element(reader, &e, &mut junk_buf)?;
+                            if let Some(properties) = extensions.properties {
+                                options = Some(properties.into());
+                            }
+                            if let Some(tag) = extensions.version_tag {
+                                version_tag = Some(tag.value.to_string());
+                            }
+                        }
+                        "laneSet" => lane_sets.push(read_element(reader, &e, &mut junk_buf)?),
+                        "textAnnotation" => events.push(BpmnEvent::TextAnnotation(read_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                        )?)),
+                        "association" => events.push(BpmnEvent::Association(read_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                        )?)),
+                        "startEvent" => events.push(BpmnEvent::StartEvent(read_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                        )?)),
+                        "endEvent" => events.push(BpmnEvent::EndEvent(read_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                        )?)),
+                        "userTask" => events.push(BpmnEvent::UserTask(read_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                        )?)),
+                        "exclusiveGateway" => events.push(BpmnEvent::ExclusiveGateway(
+                            read_element(reader, &e, &mut junk_buf)?,
+                        )),
+                        "parallelGateway" => events.push(BpmnEvent::ParallelGateway(read_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                        )?)),
+                        "inclusiveGateway" => events.push(BpmnEvent::InclusiveGateway(
+                            read_element(reader, &e, &mut junk_buf)?,
+                        )),
+                        "sequenceFlow" => {
+                            let position = start_position(reader, &e);
+                            let flow: SequenceFlow = read_element(reader, &e, &mut junk_buf)?;
+                            events.push(BpmnEvent::SequenceFlow(SequenceFlow { position, ..flow }));
+                        }
+                        "callActivity" => events.push(BpmnEvent::CallActivity(read_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                        )?)),
+                        "subProcess" => events.push(BpmnEvent::SubProcess(read_sub_process(
+                            reader,
+                            &e,
+                            &mut Vec::new(),
+                            context,
+                        )?)),
+                        "incoming" => incoming.push(read_element(reader, &e, &mut junk_buf)?),
+                        "outgoing" => outgoing.push(read_element(reader, &e, &mut junk_buf)?),
+                        _ if context.lenient => events.extend(read_unsupported_element(
+                            reader,
+                            &e,
+                            &mut junk_buf,
+                            context,
+                        )?),
+                        _ => {
+                            return Err(XmlError::UnsupportedElement {
+                                position: start_position(reader, &e),
+                                element: name.to_string(),
+                                id: element_id(&e),
+                            });
+                        }
+                    }
+                }
+            }
+            Event::End(e) if e.name() == tag_name => {
+                return Ok(FlowElements {
+                    events,
+                    incoming,
+                    outgoing,
+                    options,
+                    version_tag,
+                    lane_sets,
+                });
+            }
+            Event::Eof => {
+                return Err(XmlError::UnexpectedEof {
+                    position: Position::at(reader.buffer_position()),
+                    element: element_name(start_tag),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_xml(xml: &str, context: &mut ParseContext) -> Result<Definitions, XmlError> {
+    let result = read_xml(xml, context).map_err(|err| err.locate(xml));
+    context.warnings = std::mem::take(&mut context.warnings)
+        .into_iter()
+        .map(|warning| warning.locate(xml))
+        .collect();
+    result
+}
+
+fn read_xml(xml: &str, context: &mut ParseContext) -> Result<Definitions, XmlError> {
+    let mut reader = NsReader::from_reader(BufReader::new(std::io::Cursor::new(xml)));
+    let mut buf = Vec::new();
+    reader.trim_text(true);
+    reader.expand_empty_elements(true);
+    let mut result = Definitions::default();
+    loop {
+        buf.clear();
+        match read_event(&mut reader, &mut buf)? {
+            Event::Eof => break,
+            Event::Start(e) => {
+                if let Some(name) = bpmn_element(&reader, &e) {
+                    match name {
+                        "definitions" => (),
+                        "process" => {
+                            let mut buf = Vec::new();
+                            result.processes.push(read_process_definition(
+                                &mut reader,
+                                &e,
+                                &mut buf,
+                                xml,
+                                context,
+                            )?);
+                        }
+                        "collaboration" => {
+                            let Collaboration { participant } =
+                                read_element(&mut reader, &e, &mut Vec::new())?;
+                            result.participants.extend(participant);
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(result)
+}
+
+// `list` comes out of a BTreeMap and is sorted by id
+fn find_index(id: &Arc<str>, list: &[(Arc<str>, BpmnEvent)]) -> i32 {
+    list.binary_search_by(|(tid, _)| tid.as_ref().cmp(id.as_ref()))
+        .map(|i| i as i32)
+        .unwrap_or(-1)
+}
+
+fn find_connections(connections: &[Connection], list: &[(Arc<str>, BpmnEvent)]) -> Arc<[i32]> {
+    Arc::from(
+        connections
+            .iter()
+            .map(|c| find_index(&c.id, list))
+            .collect::<Vec<i32>>(),
+    )
+}
+
+fn parse_expression(
+    flow: &SequenceFlow,
+    expression: &BpmnExpression,
+) -> Result<Option<ConditionExpression>, XmlError> {
+    let invalid = |err: jsep::ParseError| XmlError::InvalidExpression {
+        position: flow.position,
+        element: "conditionExpression".to_string(),
+        id: flow.id.to_string(),
+        index: err.index,
+        message: err.message,
+    };
+    match expression.language.as_ref() {
+        "jsep" => Ok(Some(ConditionExpression::Jsep(
+            jsep::parse(&expression.expr).map_err(invalid)?,
+        ))),
+        _ => Ok(None),
+    }
+}
+
+struct WorkflowDefinitionBuilder {
+    id: String,
+    version: String,
+    version_source: VersionSource,
+    tasks: Vec<(Arc<str>, BpmnEvent)>,
+    flows: Vec<(Arc<str>, BpmnEvent)>,
+    options: Option<WorkflowProperties>,
+}
+
+impl WorkflowDefinitionBuilder {
+    fn new(process_definition: ProcessDefinition) -> Self {
+        Self::from_events(
+            process_definition.id,
+            process_definition.version,
+            process_definition.version_source,
+            process_definition.events,
+            process_definition.options,
+        )
+    }
+
+    fn from_events(
+        id: String,
+        version: String,
+        version_source: VersionSource,
+        events: Vec<BpmnEvent>,
+        options: Option<WorkflowProperties>,
+    ) -> Self {
+        let mut tasks: BTreeMap<Arc<str>, BpmnEvent> = BTreeMap::new();
+        let mut flows: BTreeMap<Arc<str>, BpmnEvent> = BTreeMap::new();
+        for event in events {
+            match event {
+                BpmnEvent::SequenceFlow(f) => {
+                    let event = BpmnEvent::SequenceFlow(f);
+                    flows.insert(event.id(), event);
+                }
+                BpmnEvent::TextAnnotation(_) => {}
+                BpmnEvent::Association(_) => {}
+                event => {
+                    tasks.insert(event.id(), event);
+                }
+            }
+        }
+        Self {
+            id,
+            version,
+            version_source,
+            options,
+            tasks: Vec::from_iter(tasks),
+            flows: Vec::from_iter(flows),
+        }
+    }
+
+    fn build(self) -> Result<WorkflowDefinition, XmlError> {
+        let Self {
+            mut tasks,
+            flows,
+            id,
+            version,
+            version_source,
+            options,
+        } = self;
+        let mut children = Vec::new();
+        for (_, event) in tasks.iter_mut() {
+            if let BpmnEvent::SubProcess(e) = event {
+                e.child = children.len() as i32;
+                children.push(
+                    Self::from_events(
+                        e.id.to_string(),
+                        version.clone(),
+                        version_source,
+                        std::mem::take(&mut e.events),
+                        None,
+                    )
+                    .build()?,
+                );
+            }
+        }
+        let mut start_event = -1;
+        let mut result_flows = Vec::new();
+        let mut result_flow_ids = Vec::new();
+        let mut result_tasks = Vec::new();
+        let mut result_task_ids = Vec::new();
+        for (id, (tid, event)) in tasks.iter().enumerate() {
+            match event {
+                BpmnEvent::StartEvent(e) => {
+                    start_event = id as i32;
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::StartEvent(StartEventDef {
+                            outgoing: find_connections(&e.outgoing, &flows),
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::EndEvent(e) => {
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::EndEvent(EndEventDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::UserTask(e) => {
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::UserTask(UserTaskDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                            outgoing: find_connections(&e.outgoing, &flows),
+                            schema: e
+                                .extension_elements
+                                .as_ref()
+                                .and_then(|ext| ext.properties.as_ref())
+                                .and_then(Properties::schema),
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::ExclusiveGateway(e) => {
+                    let outgoing = find_connections(&e.outgoing, &flows);
+                    let default = if let Some(default_flow) = e.default.as_ref() {
+                        find_index(default_flow, &flows)
+                    } else {
+                        *outgoing.first().unwrap_or(&-1)
+                    };
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::ExclusiveGateway(ExclusiveGatewayDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                            outgoing,
+                            default,
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::ParallelGateway(e) => {
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::ParallelGateway(ParallelGatewayDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                            outgoing: find_connections(&e.outgoing, &flows),
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::SubProcess(e) => {
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::SubProcess(SubProcessDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                            outgoing: find_connections(&e.outgoing, &flows),
+                            child: e.child,
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::CallActivity(e) => {
+                    let extensions = e.extension_elements.as_ref();
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::CallActivity(CallActivityDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                            outgoing: find_connections(&e.outgoing, &flows),
+                            called_element: e.called_element.clone(),
+                            inputs: extensions
+                                .map(|ext| ext.inputs.iter().filter_map(VariableMap::to_mapping))
+                                .into_iter()
+                                .flatten()
+                                .collect(),
+                            outputs: extensions
+                                .map(|ext| ext.outputs.iter().filter_map(VariableMap::to_mapping))
+                                .into_iter()
+                                .flatten()
+                                .collect(),
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::PassThrough(e) => {
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::PassThrough(PassThroughDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                            outgoing: find_connections(&e.outgoing, &flows),
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                BpmnEvent::InclusiveGateway(e) => {
+                    let outgoing = find_connections(&e.outgoing, &flows);
+                    let default = if let Some(default_flow) = e.default.as_ref() {
+                        find_index(default_flow, &flows)
+                    } else {
+                        *outgoing.first().unwrap_or(&-1)
+                    };
+                    result_tasks.push(Task {
+                        id: id as i32,
+                        def: TaskDef::InclusiveGateway(InclusiveGatewayDef {
+                            incoming: find_connections(&e.incoming, &flows),
+                            outgoing,
+                            default,
+                        }),
+                    });
+                    result_task_ids.push(tid.clone());
+                }
+                _ => {}
+            }
+        }
+        for (id, (fid, event)) in flows.iter().enumerate() {
+            if let BpmnEvent::SequenceFlow(f) = event {
+                result_flows.push(Flow {
+                    id: id as i32,
+                    source_ref: find_index(&f.source_ref, &tasks),
+                    target_ref: find_index(&f.target_ref, &tasks),
+                    condition_expression: match f.condition_expression.as_ref() {
+                        Some(expression) => parse_expression(f, expression)?,
+                        None => None,
+                    },
+                });
+                result_flow_ids.push(fid.clone());
+            }
+        }
+        Ok(WorkflowDefinition {
+            id: Arc::from(id),
+            start_event,
+            version: Arc::from(version),
+            version_source,
+            flows: Arc::from(result_flows),
+            flow_ids: Arc::from(result_flow_ids),
+            tasks: Arc::from(result_tasks),
+            task_ids: Arc::from(result_task_ids),
+            parent: None,
+            children: if children.is_empty() {
+                None
+            } else {
+                Some(Arc::from(children))
+            },
+            options,
+        })
+    }
+}
+
+impl TryFrom<ProcessDefinition> for WorkflowDefinition {
+    type Error = XmlError;
+    fn try_from(val: ProcessDefinition) -> Result<Self, Self::Error> {
+        WorkflowDefinitionBuilder::new(val).build()
+    }
+}
+
+impl TryFrom<Definitions> for WorkflowBundle {
+    type Error = XmlError;
+    fn try_from(val: Definitions) -> Result<Self, Self::Error> {
+        if val.processes.is_empty() {
+            return Err(XmlError::NoProcessDefinition);
+        }
+        let mut lanes = Vec::new();
+        let mut processes = Vec::new();
+        for mut process in val.processes {
+            lanes.append(&mut process.lanes);
+            processes.push(process.try_into()?);
+        }
+        Ok(WorkflowBundle::new(
+            Arc::from(processes),
+            val.participants.into_iter().map(Into::into).collect(),
+            Arc::from(lanes),
+        ))
+    }
+}
+
+fn parse_bundle(xml: &str, context: &mut ParseContext) -> Result<WorkflowBundle, XmlError> {
+    parse_xml(xml, context)?
+        .try_into()
+        .map_err(|err: XmlError| err.locate(xml))
+}
+
+pub struct Parsed {
+    pub bundle: WorkflowBundle,
+    pub warnings: Vec<ParseWarning>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Parsed {
+    pub fn has_errors(&self) -> bool {
+        has_errors(&self.diagnostics)
+    }
+
+    pub fn issues(&self) -> Vec<ParseIssue> {
+        self.warnings
+            .iter()
+            .map(Into::into)
+            .chain(self.diagnostics.iter().map(Into::into))
+            .collect()
+    }
+}
+
+pub fn parse(xml: &str, options: &ParseOptions) -> Result<Parsed, XmlError> {
+    let mut context = ParseContext::new(options);
+    let bundle = parse_bundle(xml, &mut context)?;
+    let mut diagnostics = Vec::new();
+    // empty processes only describe a participant and are never executed
+    for definition in bundle.processes.iter().filter(|d| !d.tasks.is_empty()) {
+        diagnostics.extend(validate(definition));
+        diagnostics.extend(check_conditions(definition));
+    }
+    Ok(Parsed {
+        bundle,
+        warnings: context.warnings,
+        diagnostics,
+    })
+}
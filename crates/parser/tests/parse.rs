@@ -0,0 +1,494 @@
+use wfrs_model::jsep::{JsepNode, Operator};
+use wfrs_model::{
+    deserialize_bundle, serialize_bundle, ConditionExpression, TaskDef, VersionSource,
+};
+use wfrs_parser::{export, parse, ExportOptions, ParseIssue, ParseOptions, XmlError};
+use wfrs_validator::{DiagnosticKind, Severity};
+
+#[test]
+fn pass() -> Result<(), String> {
+    let diagram = std::include_str!("./diagram.bpmn");
+    let parsed = parse(diagram, &ParseOptions::default()).map_err(|err| err.to_string())?;
+    assert!(!parsed.has_errors(), "{:?}", parsed.issues());
+    let definition = parsed.bundle.get("create-child").ok_or("missing process")?;
+    assert_eq!(definition.version.as_ref(), "2023.40.0");
+    assert_eq!(definition.tasks.len(), 12);
+    let condition = definition
+        .flows
+        .iter()
+        .find_map(|flow| flow.condition_expression.as_ref())
+        .ok_or("missing condition")?;
+    let ConditionExpression::Jsep(JsepNode::BinaryExpression(binary)) = condition else {
+        return Err(format!("unexpected condition {condition:?}"));
+    };
+    assert_eq!(binary.operator, Operator::Equal);
+    Ok(())
+}
+
+const FEATURES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:camunda="http://camunda.org/schema/1.0/bpmn" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <bpmn:process id="features" camunda:versionTag="1.2.3">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="review">
+      <bpmn:extensionElements>
+        <camunda:properties>
+          <camunda:property name="var:approved" value="boolean" />
+          <camunda:property name="var:note" value="string" />
+        </camunda:properties>
+      </bpmn:extensionElements>
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:incoming>f5</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+    </bpmn:userTask>
+    <bpmn:exclusiveGateway id="decide" default="f4">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+      <bpmn:outgoing>f4</bpmn:outgoing>
+      <bpmn:outgoing>f5</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:subProcess id="nested">
+      <bpmn:incoming>f3</bpmn:incoming>
+      <bpmn:outgoing>f6</bpmn:outgoing>
+      <bpmn:startEvent id="nested_start"><bpmn:outgoing>n1</bpmn:outgoing></bpmn:startEvent>
+      <bpmn:endEvent id="nested_end"><bpmn:incoming>n1</bpmn:incoming></bpmn:endEvent>
+      <bpmn:sequenceFlow id="n1" sourceRef="nested_start" targetRef="nested_end" />
+    </bpmn:subProcess>
+    <bpmn:callActivity id="call" calledElement="other">
+      <bpmn:extensionElements>
+        <camunda:in source="note" target="input" />
+        <camunda:out source="result" target="note" />
+      </bpmn:extensionElements>
+      <bpmn:incoming>f4</bpmn:incoming>
+      <bpmn:outgoing>f7</bpmn:outgoing>
+    </bpmn:callActivity>
+    <bpmn:endEvent id="end">
+      <bpmn:incoming>f6</bpmn:incoming>
+      <bpmn:incoming>f7</bpmn:incoming>
+    </bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="review" />
+    <bpmn:sequenceFlow id="f2" sourceRef="review" targetRef="decide" />
+    <bpmn:sequenceFlow id="f3" sourceRef="decide" targetRef="nested">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$steps.review.approved &amp;&amp; $steps.review.note != "&lt;none&gt;"</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f4" sourceRef="decide" targetRef="call" />
+    <bpmn:sequenceFlow id="f5" sourceRef="decide" targetRef="review">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">!$steps.review.approved</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="f6" sourceRef="nested" targetRef="end" />
+    <bpmn:sequenceFlow id="f7" sourceRef="call" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn export_round_trip() -> Result<(), String> {
+    for (xml, id) in [
+        (std::include_str!("./diagram.bpmn"), "create-child"),
+        (FEATURES, "features"),
+    ] {
+        let parsed = parse(xml, &ParseOptions::default()).map_err(|err| err.to_string())?;
+        let definition = parsed.bundle.get(id).ok_or("missing process")?;
+        for layout in [false, true] {
+            let exported = export(definition, &ExportOptions { layout });
+            let reparsed =
+                parse(&exported, &ParseOptions::default()).map_err(|err| err.to_string())?;
+            assert_eq!(reparsed.bundle.get(id), Some(definition), "{exported}");
+            let shapes = exported.matches("<bpmndi:BPMNShape ").count();
+            let edges = exported.matches("<bpmndi:BPMNEdge ").count();
+            if layout {
+                assert!(shapes >= definition.tasks.len(), "{exported}");
+                assert!(edges >= definition.flows.len(), "{exported}");
+            } else {
+                assert_eq!(shapes + edges, 0);
+            }
+        }
+    }
+    Ok(())
+}
+
+const CONDITIONS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:camunda="http://camunda.org/schema/1.0/bpmn" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <bpmn:process id="conditions">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="form">
+      <bpmn:extensionElements>
+        <camunda:properties>
+          <camunda:property name="var:approved" value="boolean" />
+          <camunda:property name="var:note" value="string" />
+        </camunda:properties>
+      </bpmn:extensionElements>
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+    </bpmn:userTask>
+    <bpmn:exclusiveGateway id="decide" default="ok">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>ok</bpmn:outgoing>
+      <bpmn:outgoing>typo_step</bpmn:outgoing>
+      <bpmn:outgoing>typo_field</bpmn:outgoing>
+      <bpmn:outgoing>mismatch</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:endEvent id="end">
+      <bpmn:incoming>ok</bpmn:incoming>
+      <bpmn:incoming>typo_step</bpmn:incoming>
+      <bpmn:incoming>typo_field</bpmn:incoming>
+      <bpmn:incoming>mismatch</bpmn:incoming>
+    </bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="form" />
+    <bpmn:sequenceFlow id="f2" sourceRef="form" targetRef="decide" />
+    <bpmn:sequenceFlow id="ok" sourceRef="decide" targetRef="end" />
+    <bpmn:sequenceFlow id="typo_step" sourceRef="decide" targetRef="end">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$steps.formX.approved == true</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="typo_field" sourceRef="decide" targetRef="end">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$steps.form.aproved == true</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+    <bpmn:sequenceFlow id="mismatch" sourceRef="decide" targetRef="end">
+      <bpmn:conditionExpression xsi:type="bpmn:tFormalExpression" language="jsep">$steps.form.note == 1 || $steps.form.approved == true</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn condition_diagnostics() -> Result<(), String> {
+    let parsed = parse(CONDITIONS, &ParseOptions::default()).map_err(|err| err.to_string())?;
+    let mut diagnostics: Vec<(&str, DiagnosticKind, Severity)> = parsed
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.element.as_ref(),
+                diagnostic.kind,
+                diagnostic.severity,
+            )
+        })
+        .collect();
+    diagnostics.sort_by_key(|(element, ..)| *element);
+    assert_eq!(
+        diagnostics,
+        vec![
+            ("mismatch", DiagnosticKind::TypeMismatch, Severity::Error),
+            ("typo_field", DiagnosticKind::UnknownField, Severity::Error),
+            ("typo_step", DiagnosticKind::UnknownStep, Severity::Error),
+        ]
+    );
+    assert!(parsed.has_errors());
+    Ok(())
+}
+
+fn parse_error(elements: &str) -> XmlError {
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="errors">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:endEvent id="end"><bpmn:incoming>f1</bpmn:incoming></bpmn:endEvent>
+{elements}
+  </bpmn:process>
+</bpmn:definitions>"#
+    );
+    match parse(&xml, &ParseOptions::default()) {
+        Ok(_) => panic!("expected a parse error"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn error_positions() {
+    let err = parse_error(r#"    <bpmn:serviceTask id="service"></bpmn:serviceTask>"#);
+    assert_eq!(err.kind(), "UnsupportedElement");
+    assert_eq!(err.element(), Some("serviceTask"));
+    assert_eq!(err.id(), Some("service"));
+    let position = err.position().unwrap();
+    assert_eq!((position.line, position.column), (6, 5));
+
+    let err = parse_error(
+        r#"    <bpmn:sequenceFlow sourceRef="start" targetRef="end"></bpmn:sequenceFlow>"#,
+    );
+    assert_eq!(err.kind(), "Deserialize");
+    assert_eq!(err.element(), Some("sequenceFlow"));
+    let position = err.position().unwrap();
+    assert_eq!((position.line, position.column), (6, 5));
+
+    let err = parse_error(
+        r#"    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="end">
+      <bpmn:conditionExpression language="jsep">$steps.form.approved ==</bpmn:conditionExpression>
+    </bpmn:sequenceFlow>"#,
+    );
+    assert_eq!(err.kind(), "InvalidExpression");
+    assert_eq!(err.element(), Some("conditionExpression"));
+    assert_eq!(err.id(), Some("f1"));
+    let position = err.position().unwrap();
+    assert_eq!((position.line, position.column), (7, 7));
+    let XmlError::InvalidExpression { index, .. } = err else {
+        unreachable!()
+    };
+    assert_eq!(index, 23);
+
+    let issue = ParseIssue::from(&parse_error("    <bpmn:userTask id=\"open\">"));
+    assert_eq!(issue.kind, "Read");
+    assert!(issue.line.is_some());
+}
+
+const UNSUPPORTED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="unsupported">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:serviceTask id="notify">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+    </bpmn:serviceTask>
+    <bpmn:dataObjectReference id="attachment" dataObjectRef="object" />
+    <bpmn:dataObject id="object" />
+    <bpmn:group id="group" />
+    <bpmn:endEvent id="end"><bpmn:incoming>f2</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="notify" />
+    <bpmn:sequenceFlow id="f2" sourceRef="notify" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn lenient() -> Result<(), String> {
+    let err = parse(UNSUPPORTED, &ParseOptions::default())
+        .err()
+        .ok_or("expected an error")?;
+    assert_eq!(err.kind(), "UnsupportedElement");
+    assert_eq!(err.id(), Some("notify"));
+
+    let options = ParseOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    let parsed = parse(UNSUPPORTED, &options).map_err(|err| err.to_string())?;
+    assert!(!parsed.has_errors(), "{:?}", parsed.issues());
+    let warnings: Vec<_> = parsed
+        .warnings
+        .iter()
+        .map(|warning| (warning.id.as_deref(), warning.kind()))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (Some("notify"), "PassThroughElement"),
+            (Some("attachment"), "SkippedElement"),
+            (Some("object"), "SkippedElement"),
+            (Some("group"), "SkippedElement"),
+        ]
+    );
+    let definition = parsed.bundle.get("unsupported").ok_or("missing process")?;
+    let notify = definition
+        .task_ids
+        .iter()
+        .position(|id| id.as_ref() == "notify")
+        .ok_or("missing pass-through task")?;
+    let TaskDef::PassThrough(pass_through) = &definition.tasks[notify].def else {
+        return Err(format!("unexpected task {:?}", definition.tasks[notify]));
+    };
+    assert_eq!(pass_through.incoming.len(), 1);
+    assert_eq!(pass_through.outgoing.len(), 1);
+    assert!(!definition.task_ids.iter().any(|id| id.as_ref() == "group"));
+    Ok(())
+}
+
+const EVENT_GATEWAY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="event_gateway">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:eventBasedGateway id="wait">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+    </bpmn:eventBasedGateway>
+    <bpmn:intermediateCatchEvent id="message">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>f4</bpmn:outgoing>
+    </bpmn:intermediateCatchEvent>
+    <bpmn:intermediateCatchEvent id="timeout">
+      <bpmn:incoming>f3</bpmn:incoming>
+      <bpmn:outgoing>f5</bpmn:outgoing>
+    </bpmn:intermediateCatchEvent>
+    <bpmn:endEvent id="end">
+      <bpmn:incoming>f4</bpmn:incoming>
+      <bpmn:incoming>f5</bpmn:incoming>
+    </bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="wait" />
+    <bpmn:sequenceFlow id="f2" sourceRef="wait" targetRef="message" />
+    <bpmn:sequenceFlow id="f3" sourceRef="wait" targetRef="timeout" />
+    <bpmn:sequenceFlow id="f4" sourceRef="message" targetRef="end" />
+    <bpmn:sequenceFlow id="f5" sourceRef="timeout" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn lenient_control_flow() {
+    let options = ParseOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    // an event based gateway picks one branch, passing through would take both
+    let err = parse(EVENT_GATEWAY, &options).err().unwrap();
+    assert_eq!(err.kind(), "UnsupportedElement");
+    assert_eq!(err.element(), Some("eventBasedGateway"));
+    assert_eq!(err.id(), Some("wait"));
+
+    // gateways are rejected even on a single path
+    let single = EVENT_GATEWAY.replace(
+        "<bpmn:outgoing>f3</bpmn:outgoing>\n    </bpmn:eventBasedGateway>",
+        "</bpmn:eventBasedGateway>",
+    );
+    assert_ne!(single, EVENT_GATEWAY);
+    let err = parse(&single, &options).err().unwrap();
+    assert_eq!(err.element(), Some("eventBasedGateway"));
+
+    // a task that merges two paths would never wait for both
+    let merge = UNSUPPORTED.replace(
+        "<bpmn:incoming>f1</bpmn:incoming>\n      <bpmn:outgoing>f2</bpmn:outgoing>\n    </bpmn:serviceTask>",
+        "<bpmn:incoming>f1</bpmn:incoming>\n      <bpmn:incoming>f3</bpmn:incoming>\n      <bpmn:outgoing>f2</bpmn:outgoing>\n    </bpmn:serviceTask>",
+    );
+    assert_ne!(merge, UNSUPPORTED);
+    let err = parse(&merge, &options).err().unwrap();
+    assert_eq!(err.kind(), "UnsupportedElement");
+    assert_eq!(err.id(), Some("notify"));
+}
+
+#[test]
+fn namespaces() -> Result<(), String> {
+    let unprefixed = r#"<?xml version="1.0" encoding="UTF-8"?>
+<definitions xmlns="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:zeebe="http://camunda.org/schema/zeebe/1.0">
+  <process id="unprefixed">
+    <extensionElements><zeebe:versionTag value="4.5.6" /></extensionElements>
+    <startEvent id="start"><outgoing>f1</outgoing></startEvent>
+    <endEvent id="end"><incoming>f1</incoming></endEvent>
+    <sequenceFlow id="f1" sourceRef="start" targetRef="end" />
+  </process>
+</definitions>"#;
+    let prefixed = r#"<?xml version="1.0" encoding="UTF-8"?>
+<semantic:definitions xmlns:semantic="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:vendor="http://camunda.org/schema/1.0/bpmn" xmlns:bpmn="urn:not-bpmn">
+  <semantic:process id="prefixed" vendor:versionTag="7.8.9">
+    <semantic:startEvent id="start"><semantic:outgoing>f1</semantic:outgoing></semantic:startEvent>
+    <bpmn:serviceTask id="foreign" />
+    <semantic:endEvent id="end"><semantic:incoming>f1</semantic:incoming></semantic:endEvent>
+    <semantic:sequenceFlow id="f1" sourceRef="start" targetRef="end" />
+  </semantic:process>
+</semantic:definitions>"#;
+    for (xml, id, version) in [
+        (unprefixed, "unprefixed", "4.5.6"),
+        (prefixed, "prefixed", "7.8.9"),
+    ] {
+        let parsed = parse(xml, &ParseOptions::default()).map_err(|err| err.to_string())?;
+        assert!(!parsed.has_errors(), "{:?}", parsed.issues());
+        assert!(parsed.warnings.is_empty());
+        let definition = parsed.bundle.get(id).ok_or("missing process")?;
+        assert_eq!(definition.version.as_ref(), version);
+        assert_eq!(definition.version_source, VersionSource::Tag);
+        assert_eq!(definition.tasks.len(), 2);
+    }
+    Ok(())
+}
+
+const COLLABORATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:collaboration id="collaboration">
+    <bpmn:participant id="customer" name="Customer" processRef="external" />
+    <bpmn:participant id="office" name="Office" processRef="order" />
+  </bpmn:collaboration>
+  <bpmn:process id="external" />
+  <bpmn:process id="order">
+    <bpmn:laneSet id="lanes">
+      <bpmn:lane id="clerk" name="Clerk">
+        <bpmn:flowNodeRef>start</bpmn:flowNodeRef>
+        <bpmn:flowNodeRef>check</bpmn:flowNodeRef>
+      </bpmn:lane>
+      <bpmn:lane id="manager" name="Manager">
+        <bpmn:flowNodeRef>end</bpmn:flowNodeRef>
+      </bpmn:lane>
+    </bpmn:laneSet>
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="check"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end"><bpmn:incoming>f2</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="check" />
+    <bpmn:sequenceFlow id="f2" sourceRef="check" targetRef="end" />
+  </bpmn:process>
+  <bpmn:process id="archive">
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:endEvent id="end"><bpmn:incoming>f1</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="end" />
+  </bpmn:process>
+</bpmn:definitions>"#;
+
+#[test]
+fn bundle() -> Result<(), String> {
+    let parsed = parse(COLLABORATION, &ParseOptions::default()).map_err(|err| err.to_string())?;
+    assert!(!parsed.has_errors(), "{:?}", parsed.issues());
+    let bundle =
+        deserialize_bundle(&serialize_bundle(parsed.bundle)).map_err(|err| err.to_string())?;
+    assert_eq!(bundle.processes.len(), 3);
+    for id in ["external", "order", "archive"] {
+        let definition = bundle.get(id).ok_or("missing process")?;
+        assert_eq!(definition.id.as_ref(), id);
+    }
+    assert_eq!(bundle.get("missing"), None);
+    // the participant-only process has no tasks and is skipped as the root
+    assert_eq!(bundle.root().map(|root| root.id.as_ref()), Some("order"));
+    assert_eq!(bundle.get("archive").map(|d| d.tasks.len()), Some(2));
+
+    let participant = bundle.participant("order").ok_or("missing participant")?;
+    assert_eq!(participant.id.as_ref(), "office");
+    assert_eq!(participant.name.as_deref(), Some("Office"));
+    assert_eq!(bundle.participant("archive"), None);
+    assert_eq!(bundle.lanes("order").count(), 2);
+    assert_eq!(
+        bundle.lane("order", "check").map(|lane| lane.id.as_ref()),
+        Some("clerk")
+    );
+    assert_eq!(
+        bundle.lane("order", "end").map(|lane| lane.id.as_ref()),
+        Some("manager")
+    );
+    assert_eq!(bundle.lane("archive", "end"), None);
+    Ok(())
+}
+
+fn versioned(tag: &str, name: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL" xmlns:camunda="http://camunda.org/schema/1.0/bpmn">
+  <bpmn:process id="versioned"{tag}>
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:endEvent id="{name}"><bpmn:incoming>f1</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="{name}" />
+  </bpmn:process>
+</bpmn:definitions>"#
+    )
+}
+
+fn version(xml: &str, supplied: Option<&str>) -> Result<(String, VersionSource), String> {
+    let options = ParseOptions {
+        version: supplied.map(ToString::to_string),
+        ..Default::default()
+    };
+    let parsed = parse(xml, &options).map_err(|err| err.to_string())?;
+    let definition = parsed.bundle.get("versioned").ok_or("missing process")?;
+    Ok((definition.version.to_string(), definition.version_source))
+}
+
+#[test]
+fn versions() -> Result<(), String> {
+    let tagged = versioned(r#" camunda:versionTag="2.0.0""#, "end");
+    let untagged = versioned("", "end");
+    // the tag wins over a supplied version
+    assert_eq!(
+        version(&tagged, Some("9.9.9"))?,
+        ("2.0.0".to_string(), VersionSource::Tag)
+    );
+    assert_eq!(
+        version(&untagged, Some("9.9.9"))?,
+        ("9.9.9".to_string(), VersionSource::Supplied)
+    );
+    let (hash, source) = version(&untagged, None)?;
+    assert_eq!(source, VersionSource::ContentHash);
+    assert_eq!(hash.len(), 16);
+    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+    // the hash is stable for the same process and changes with its content
+    assert_eq!(version(&untagged, None)?.0, hash);
+    assert_ne!(version(&versioned("", "done"), None)?.0, hash);
+    Ok(())
+}
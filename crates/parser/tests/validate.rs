@@ -0,0 +1,203 @@
+use wfrs_parser::{parse, ParseOptions};
+use wfrs_validator::{DiagnosticKind, Severity};
+
+fn process(elements: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<bpmn:definitions xmlns:bpmn="http://www.omg.org/spec/BPMN/20100524/MODEL">
+  <bpmn:process id="structure">{elements}</bpmn:process>
+</bpmn:definitions>"#
+    )
+}
+
+fn diagnostics(elements: &str) -> Vec<(String, DiagnosticKind, Severity)> {
+    let parsed = parse(&process(elements), &ParseOptions::default()).unwrap();
+    let mut diagnostics: Vec<_> = parsed
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.element.to_string(),
+                diagnostic.kind,
+                diagnostic.severity,
+            )
+        })
+        .collect();
+    diagnostics.sort_by(|a, b| a.0.cmp(&b.0));
+    diagnostics
+}
+
+fn element(
+    id: &str,
+    kind: DiagnosticKind,
+    severity: Severity,
+) -> (String, DiagnosticKind, Severity) {
+    (id.to_string(), kind, severity)
+}
+
+#[test]
+fn valid() {
+    let elements = r#"
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="task"><bpmn:incoming>f1</bpmn:incoming><bpmn:outgoing>f2</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end"><bpmn:incoming>f2</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="task" />
+    <bpmn:sequenceFlow id="f2" sourceRef="task" targetRef="end" />"#;
+    assert_eq!(diagnostics(elements), vec![]);
+}
+
+#[test]
+fn missing_references() {
+    let elements = r#"
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:endEvent id="end"><bpmn:incoming>f1</bpmn:incoming><bpmn:incoming>missing</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="nowhere" />"#;
+    assert_eq!(
+        diagnostics(elements),
+        vec![
+            element("end", DiagnosticKind::UnresolvedReference, Severity::Error),
+            element("end", DiagnosticKind::UnreachableTask, Severity::Warning),
+            element("f1", DiagnosticKind::UnresolvedReference, Severity::Error),
+            element("start", DiagnosticKind::DeadEnd, Severity::Error),
+        ]
+    );
+}
+
+#[test]
+fn start_events() {
+    let elements = r#"
+    <bpmn:startEvent id="first"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:startEvent id="second"><bpmn:outgoing>f2</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:endEvent id="end"><bpmn:incoming>f1</bpmn:incoming><bpmn:incoming>f2</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="first" targetRef="end" />
+    <bpmn:sequenceFlow id="f2" sourceRef="second" targetRef="end" />"#;
+    assert_eq!(
+        diagnostics(elements),
+        vec![element(
+            "structure",
+            DiagnosticKind::MultipleStartEvents,
+            Severity::Error
+        )]
+    );
+
+    let elements = r#"
+    <bpmn:userTask id="task"><bpmn:incoming>f2</bpmn:incoming><bpmn:outgoing>f1</bpmn:outgoing></bpmn:userTask>
+    <bpmn:exclusiveGateway id="again" default="f2">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:endEvent id="end"><bpmn:incoming>f3</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="task" targetRef="again" />
+    <bpmn:sequenceFlow id="f2" sourceRef="again" targetRef="task" />
+    <bpmn:sequenceFlow id="f3" sourceRef="again" targetRef="end" />"#;
+    let diagnostics = diagnostics(elements);
+    assert!(diagnostics.contains(&element(
+        "structure",
+        DiagnosticKind::MissingStartEvent,
+        Severity::Error
+    )));
+    assert!(diagnostics.contains(&element(
+        "task",
+        DiagnosticKind::UnreachableTask,
+        Severity::Warning
+    )));
+}
+
+#[test]
+fn unreachable_and_dead_end() {
+    let elements = r#"
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:userTask id="stuck">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:incoming>f3</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+    </bpmn:userTask>
+    <bpmn:exclusiveGateway id="retry"><bpmn:incoming>f2</bpmn:incoming><bpmn:outgoing>f3</bpmn:outgoing></bpmn:exclusiveGateway>
+    <bpmn:userTask id="orphan"><bpmn:incoming>f5</bpmn:incoming><bpmn:outgoing>f4</bpmn:outgoing></bpmn:userTask>
+    <bpmn:endEvent id="end"><bpmn:incoming>f4</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="stuck" />
+    <bpmn:sequenceFlow id="f2" sourceRef="stuck" targetRef="retry" />
+    <bpmn:sequenceFlow id="f3" sourceRef="retry" targetRef="stuck" />
+    <bpmn:sequenceFlow id="f4" sourceRef="orphan" targetRef="end" />"#;
+    assert_eq!(
+        diagnostics(elements),
+        vec![
+            element("end", DiagnosticKind::UnreachableTask, Severity::Warning),
+            element(
+                "orphan",
+                DiagnosticKind::UnresolvedReference,
+                Severity::Error
+            ),
+            element("orphan", DiagnosticKind::UnreachableTask, Severity::Warning),
+            element("retry", DiagnosticKind::DeadEnd, Severity::Error),
+            element("start", DiagnosticKind::DeadEnd, Severity::Error),
+            element("stuck", DiagnosticKind::DeadEnd, Severity::Error),
+        ]
+    );
+}
+
+#[test]
+fn cycles() {
+    // the loop through `check` and `again` never waits for a user
+    let elements = r#"
+    <bpmn:startEvent id="start"><bpmn:outgoing>f1</bpmn:outgoing></bpmn:startEvent>
+    <bpmn:exclusiveGateway id="check" default="f2">
+      <bpmn:incoming>f1</bpmn:incoming>
+      <bpmn:incoming>f3</bpmn:incoming>
+      <bpmn:outgoing>f2</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:exclusiveGateway id="again" default="f3">
+      <bpmn:incoming>f2</bpmn:incoming>
+      <bpmn:outgoing>f3</bpmn:outgoing>
+      <bpmn:outgoing>f4</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:userTask id="review">
+      <bpmn:incoming>f4</bpmn:incoming>
+      <bpmn:incoming>f6</bpmn:incoming>
+      <bpmn:outgoing>f5</bpmn:outgoing>
+    </bpmn:userTask>
+    <bpmn:exclusiveGateway id="decide" default="f6">
+      <bpmn:incoming>f5</bpmn:incoming>
+      <bpmn:outgoing>f6</bpmn:outgoing>
+      <bpmn:outgoing>f7</bpmn:outgoing>
+    </bpmn:exclusiveGateway>
+    <bpmn:endEvent id="end"><bpmn:incoming>f7</bpmn:incoming></bpmn:endEvent>
+    <bpmn:sequenceFlow id="f1" sourceRef="start" targetRef="check" />
+    <bpmn:sequenceFlow id="f2" sourceRef="check" targetRef="again" />
+    <bpmn:sequenceFlow id="f3" sourceRef="again" targetRef="check" />
+    <bpmn:sequenceFlow id="f4" sourceRef="again" targetRef="review" />
+    <bpmn:sequenceFlow id="f5" sourceRef="review" targetRef="decide" />
+    <bpmn:sequenceFlow id="f6" sourceRef="decide" targetRef="review" />
+    <bpmn:sequenceFlow id="f7" sourceRef="decide" targetRef="end" />"#;
+    let diagnostics = diagnostics(elements);
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0].1, DiagnosticKind::InfiniteLoop);
+    assert!(["check", "again"].contains(&diagnostics[0].0.as_str()));
+}
+
+#[test]
+fn long_process() {
+    let length = 20_000;
+    let mut elements = String::from(
+        r#"<bpmn:startEvent id="start"><bpmn:outgoing>f0</bpmn:outgoing></bpmn:startEvent>"#,
+    );
+    for step in 0..length {
+        elements.push_str(&format!(
+            r#"<bpmn:userTask id="t{step}"><bpmn:incoming>f{step}</bpmn:incoming><bpmn:outgoing>f{}</bpmn:outgoing></bpmn:userTask>
+<bpmn:sequenceFlow id="f{step}" sourceRef="{}" targetRef="t{step}" />"#,
+            step + 1,
+            if step == 0 {
+                "start".to_string()
+            } else {
+                format!("t{}", step - 1)
+            },
+        ));
+    }
+    elements.push_str(&format!(
+        r#"<bpmn:endEvent id="end"><bpmn:incoming>f{length}</bpmn:incoming></bpmn:endEvent>
+<bpmn:sequenceFlow id="f{length}" sourceRef="t{}" targetRef="end" />"#,
+        length - 1
+    ));
+    assert_eq!(diagnostics(&elements), vec![]);
+}
@@ -0,0 +1,224 @@
+use std::{fmt, sync::Arc};
+use wfrs_model::{
+    jsep::{JsepNode, MemberExpression, Operator, UnaryOperator},
+    json::JsonValue,
+    ConditionExpression, TaskDef, VariableType, WorkflowDefinition,
+};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Type {
+    Unknown,
+    Null,
+    Boolean,
+    Number,
+    String,
+    Object,
+    Array,
+}
+
+impl From<VariableType> for Type {
+    fn from(value: VariableType) -> Self {
+        match value {
+            VariableType::Any => Type::Unknown,
+            VariableType::Boolean => Type::Boolean,
+            VariableType::Number => Type::Number,
+            VariableType::String => Type::String,
+            VariableType::Object => Type::Object,
+            VariableType::Array => Type::Array,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Unknown => write!(f, "unknown"),
+            Type::Null => write!(f, "null"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Object => write!(f, "object"),
+            Type::Array => write!(f, "array"),
+        }
+    }
+}
+
+pub fn check_conditions(definition: &WorkflowDefinition) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        scopes: Vec::new(),
+        element: Arc::from(""),
+        diagnostics: Vec::new(),
+    };
+    checker.check_definition(definition);
+    checker.diagnostics
+}
+
+struct Checker<'d> {
+    scopes: Vec<&'d WorkflowDefinition>,
+    element: Arc<str>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'d> Checker<'d> {
+    fn check_definition(&mut self, definition: &'d WorkflowDefinition) {
+        self.scopes.push(definition);
+        for (flow, id) in definition.flows.iter().zip(definition.flow_ids.iter()) {
+            if let Some(ConditionExpression::Jsep(node)) = &flow.condition_expression {
+                self.element = id.clone();
+                self.infer(node);
+            }
+        }
+        for task in definition.tasks.iter() {
+            if let Some(child) = definition.sub_process(task.id) {
+                self.check_definition(child);
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn error(&mut self, kind: DiagnosticKind, message: String) {
+        self.diagnostics
+            .push(Diagnostic::error(kind, self.element.clone(), message));
+    }
+
+    fn step(&self, name: &str) -> Option<&'d TaskDef> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.task_by_id(name))
+            .map(|task| &task.def)
+    }
+
+    fn infer(&mut self, node: &JsepNode) -> Type {
+        match node {
+            JsepNode::Literal(lit) => match &lit.value {
+                JsonValue::Null => Type::Null,
+                JsonValue::Bool(_) => Type::Boolean,
+                JsonValue::Number(_) => Type::Number,
+                JsonValue::String(_) => Type::String,
+                JsonValue::Array(_) => Type::Array,
+                JsonValue::Object(_) => Type::Object,
+            },
+            JsepNode::Identifier(identifier) => match identifier.name.as_ref() {
+                "$steps" | "$vars" | "$env" | "$instance" => Type::Object,
+                name => {
+                    self.error(
+                        DiagnosticKind::UnknownIdentifier,
+                        format!("unknown identifier '{name}'"),
+                    );
+                    Type::Unknown
+                }
+            },
+            JsepNode::MemberExpression(member) => self.infer_member(member),
+            JsepNode::BinaryExpression(binary) => {
+                let left = self.infer(&binary.left);
+                let right = self.infer(&binary.right);
+                match binary.operator {
+                    Operator::And | Operator::Or if left == right => left,
+                    Operator::And | Operator::Or => Type::Unknown,
+                    Operator::Add if left == Type::String || right == Type::String => Type::String,
+                    Operator::Add
+                    | Operator::Sub
+                    | Operator::Mul
+                    | Operator::Div
+                    | Operator::Mod => Type::Number,
+                    _ => {
+                        let known = |t: Type| t != Type::Unknown && t != Type::Null;
+                        if known(left) && known(right) && left != right {
+                            self.error(
+                                DiagnosticKind::TypeMismatch,
+                                format!("comparison between {left} and {right}"),
+                            );
+                        }
+                        Type::Boolean
+                    }
+                }
+            }
+            JsepNode::UnaryExpression(unary) => {
+                self.infer(&unary.argument);
+                match unary.operator {
+                    UnaryOperator::Not => Type::Boolean,
+                    UnaryOperator::Negate | UnaryOperator::Plus => Type::Number,
+                }
+            }
+            JsepNode::CallExpression(call) => {
+                for argument in call.arguments.iter() {
+                    self.infer(argument);
+                }
+                Type::Unknown
+            }
+            JsepNode::ConditionalExpression(conditional) => {
+                self.infer(&conditional.test);
+                let consequent = self.infer(&conditional.consequent);
+                let alternate = self.infer(&conditional.alternate);
+                if consequent == alternate {
+                    consequent
+                } else {
+                    Type::Unknown
+                }
+            }
+        }
+    }
+
+    fn infer_member(&mut self, member: &MemberExpression) -> Type {
+        if let Some(step) = step_name(member) {
+            if self.step(step).is_none() {
+                self.error(
+                    DiagnosticKind::UnknownStep,
+                    format!("unknown step '{step}'"),
+                );
+                return Type::Unknown;
+            }
+            return Type::Object;
+        }
+        let object = self.infer(&member.object);
+        if member.computed {
+            self.infer(&member.property);
+        }
+        let JsepNode::MemberExpression(parent) = member.object.as_ref() else {
+            return Type::Unknown;
+        };
+        let (Some(step), Some(field)) = (step_name(parent), property_name(member)) else {
+            return Type::Unknown;
+        };
+        if object == Type::Unknown {
+            return Type::Unknown;
+        }
+        let Some(TaskDef::UserTask(task)) = self.step(step) else {
+            return Type::Unknown;
+        };
+        let Some(schema) = task.schema.as_ref() else {
+            return Type::Unknown;
+        };
+        match schema
+            .iter()
+            .find(|variable| variable.name.as_ref() == field)
+        {
+            Some(variable) => variable.kind.into(),
+            None => {
+                self.error(
+                    DiagnosticKind::UnknownField,
+                    format!("unknown field '{field}' on step '{step}'"),
+                );
+                Type::Unknown
+            }
+        }
+    }
+}
+
+fn property_name(member: &MemberExpression) -> Option<&str> {
+    match (member.computed, member.property.as_ref()) {
+        (false, JsepNode::Identifier(property)) => Some(property.name.as_ref()),
+        (true, JsepNode::Literal(property)) => property.value.as_str(),
+        _ => None,
+    }
+}
+
+fn step_name(member: &MemberExpression) -> Option<&str> {
+    match member.object.as_ref() {
+        JsepNode::Identifier(root) if root.name.as_ref() == "$steps" => property_name(member),
+        _ => None,
+    }
+}
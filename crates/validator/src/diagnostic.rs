@@ -0,0 +1,73 @@
+use std::{fmt, sync::Arc};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    UnknownIdentifier,
+    UnknownStep,
+    UnknownField,
+    TypeMismatch,
+    UnresolvedReference,
+    MissingStartEvent,
+    MultipleStartEvents,
+    UnreachableTask,
+    DeadEnd,
+    InvalidDefault,
+    InfiniteLoop,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub element: Arc<str>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn warning(kind: DiagnosticKind, element: Arc<str>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            kind,
+            element,
+            message,
+        }
+    }
+
+    pub fn error(kind: DiagnosticKind, element: Arc<str>, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            kind,
+            element,
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]: {}", self.severity, self.element, self.message)
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(Diagnostic::is_error)
+}
@@ -0,0 +1,396 @@
+use std::{borrow::Cow, fmt};
+
+use crate::functions::FunctionRegistry;
+use wfrs_model::{
+    jsep::{
+        BinaryExpression, CallExpression, ConditionalExpression, ExpressionIdentifier, JsepNode,
+        MemberExpression, Operator, UnaryExpression, UnaryOperator,
+    },
+    json::{JsonNumber, JsonValue},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+    UnknownIdentifier(String),
+    UnknownFunction(String),
+    InvalidCallee,
+    NullAccess(String),
+    Function { name: String, message: String },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownIdentifier(name) => write!(f, "unknown identifier '{name}'"),
+            Self::UnknownFunction(name) => write!(f, "unknown function '{name}'"),
+            Self::InvalidCallee => write!(f, "only named functions can be called"),
+            Self::NullAccess(property) => {
+                write!(f, "cannot read property '{property}' of null")
+            }
+            Self::Function { name, message } => write!(f, "{name}() failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+pub const PROCESS_VARIABLES: &str = "$vars";
+
+static NULL: JsonValue = JsonValue::Null;
+
+#[derive(Clone, Copy)]
+pub struct Context<'v> {
+    pub variables: &'v JsonValue,
+    pub env: &'v JsonValue,
+    pub instance: &'v JsonValue,
+    pub functions: &'v FunctionRegistry,
+}
+
+impl<'v> Context<'v> {
+    pub fn new(variables: &'v JsonValue, functions: &'v FunctionRegistry) -> Self {
+        Self {
+            variables,
+            env: &NULL,
+            instance: &NULL,
+            functions,
+        }
+    }
+
+    pub fn with_env(mut self, env: &'v JsonValue) -> Self {
+        self.env = env;
+        self
+    }
+
+    pub fn with_instance(mut self, instance: &'v JsonValue) -> Self {
+        self.instance = instance;
+        self
+    }
+}
+
+pub type EvalResult<'v> = Result<Cow<'v, JsonValue>, EvalError>;
+
+pub fn is_truthy(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Null => false,
+        JsonValue::Bool(b) => *b,
+        JsonValue::Number(n) => {
+            let n = n.as_f64();
+            n != 0.0 && !n.is_nan()
+        }
+        JsonValue::String(s) => !s.is_empty(),
+        JsonValue::Array(_) | JsonValue::Object(_) => true,
+    }
+}
+
+pub fn to_number(value: &JsonValue) -> f64 {
+    match value {
+        JsonValue::Null => 0.0,
+        JsonValue::Bool(b) => *b as u8 as f64,
+        JsonValue::Number(n) => n.as_f64(),
+        JsonValue::String(s) if s.trim().is_empty() => 0.0,
+        JsonValue::String(s) => s.trim().parse().unwrap_or(f64::NAN),
+        JsonValue::Array(_) | JsonValue::Object(_) => f64::NAN,
+    }
+}
+
+pub fn to_string(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => "null".to_string(),
+        JsonValue::Bool(b) => b.to_string(),
+        JsonValue::Number(n) => n.as_f64().to_string(),
+        JsonValue::String(s) => s.clone(),
+        JsonValue::Array(a) => a.iter().map(to_string).collect::<Vec<_>>().join(","),
+        JsonValue::Object(_) => "[object Object]".to_string(),
+    }
+}
+
+fn number(value: f64) -> JsonValue {
+    JsonValue::Number(JsonNumber::from(value))
+}
+
+fn lookup<'x>(object: &'x JsonValue, key: &JsonValue) -> Cow<'x, JsonValue> {
+    // like in javascript `a['1']` is the same index as `a[1]`, but `a['01']` is not
+    let index = match key {
+        JsonValue::Number(n) => Some(n.as_f64()),
+        JsonValue::String(s) => s
+            .parse::<u32>()
+            .ok()
+            .filter(|n| n.to_string() == *s)
+            .map(f64::from),
+        _ => None,
+    }
+    .filter(|n| n.fract() == 0.0 && *n >= 0.0);
+    let found = match (object, index) {
+        (JsonValue::Array(a), Some(index)) => a.get(index as usize),
+        (JsonValue::String(s), Some(index)) => {
+            return match s.chars().nth(index as usize) {
+                Some(c) => Cow::Owned(JsonValue::String(c.to_string())),
+                None => Cow::Owned(JsonValue::Null),
+            }
+        }
+        (JsonValue::Array(a), None) if key.as_str() == Some("length") => {
+            return Cow::Owned(number(a.len() as f64))
+        }
+        (JsonValue::String(s), None) if key.as_str() == Some("length") => {
+            return Cow::Owned(number(s.chars().count() as f64))
+        }
+        (JsonValue::Object(o), _) => o.get(&to_string(key)),
+        _ => None,
+    };
+    match found {
+        Some(value) => Cow::Borrowed(value),
+        None => Cow::Owned(JsonValue::Null),
+    }
+}
+
+pub struct Expression<'a>(pub &'a JsepNode);
+
+impl<'a> Expression<'a> {
+    pub fn evaluate<'v>(&self, ctx: &Context<'v>) -> EvalResult<'v>
+    where
+        'a: 'v,
+    {
+        match self.0 {
+            JsepNode::Literal(lit) => Ok(Cow::Borrowed(&lit.value)),
+            JsepNode::Identifier(identifier) => Identifier(identifier).resolve(ctx),
+            JsepNode::MemberExpression(member) => Member(member).resolve(ctx),
+            JsepNode::BinaryExpression(binary) => Binary(binary).evaluate(ctx),
+            JsepNode::UnaryExpression(unary) => Unary(unary).evaluate(ctx),
+            JsepNode::CallExpression(call) => Call(call).evaluate(ctx),
+            JsepNode::ConditionalExpression(conditional) => Conditional(conditional).evaluate(ctx),
+        }
+    }
+}
+
+pub struct Identifier<'a>(&'a ExpressionIdentifier);
+
+impl<'a> Identifier<'a> {
+    fn resolve<'v>(&self, ctx: &Context<'v>) -> EvalResult<'v> {
+        match self.0.name.as_ref() {
+            "$steps" => Ok(Cow::Borrowed(ctx.variables)),
+            PROCESS_VARIABLES => Ok(Cow::Borrowed(
+                ctx.variables.get_path(PROCESS_VARIABLES).unwrap_or(&NULL),
+            )),
+            "$env" => Ok(Cow::Borrowed(ctx.env)),
+            "$instance" => Ok(Cow::Borrowed(ctx.instance)),
+            name => Err(EvalError::UnknownIdentifier(name.to_string())),
+        }
+    }
+}
+
+pub struct Member<'a>(&'a MemberExpression);
+
+impl<'a> Member<'a> {
+    fn resolve<'v>(&self, ctx: &Context<'v>) -> EvalResult<'v>
+    where
+        'a: 'v,
+    {
+        Ok(self.access(ctx)?.unwrap_or(Cow::Owned(JsonValue::Null)))
+    }
+
+    fn access<'v>(&self, ctx: &Context<'v>) -> Result<Option<Cow<'v, JsonValue>>, EvalError>
+    where
+        'a: 'v,
+    {
+        let object = match self.0.object.as_ref() {
+            JsepNode::MemberExpression(member) => match Member(member).access(ctx)? {
+                Some(object) => object,
+                None => return Ok(None),
+            },
+            object => Expression(object).evaluate(ctx)?,
+        };
+        if object.is_null() && self.0.optional {
+            return Ok(None);
+        }
+        let key = match (self.0.computed, self.0.property.as_ref()) {
+            (false, JsepNode::Identifier(property)) => {
+                Cow::Owned(JsonValue::String(property.name.to_string()))
+            }
+            (_, property) => Expression(property).evaluate(ctx)?,
+        };
+        if object.is_null() {
+            return Err(EvalError::NullAccess(to_string(&key)));
+        }
+        Ok(Some(match object {
+            Cow::Borrowed(object) => lookup(object, &key),
+            Cow::Owned(object) => Cow::Owned(lookup(&object, &key).into_owned()),
+        }))
+    }
+}
+
+pub struct Conditional<'a>(&'a ConditionalExpression);
+
+impl<'a> Conditional<'a> {
+    fn evaluate<'v>(&self, ctx: &Context<'v>) -> EvalResult<'v>
+    where
+        'a: 'v,
+    {
+        let test = Expression(&self.0.test).evaluate(ctx)?;
+        if is_truthy(&test) {
+            Expression(&self.0.consequent).evaluate(ctx)
+        } else {
+            Expression(&self.0.alternate).evaluate(ctx)
+        }
+    }
+}
+
+pub struct Call<'a>(&'a CallExpression);
+
+impl<'a> Call<'a> {
+    fn evaluate<'v>(&self, ctx: &Context<'v>) -> EvalResult<'v>
+    where
+        'a: 'v,
+    {
+        let JsepNode::Identifier(callee) = self.0.callee.as_ref() else {
+            return Err(EvalError::InvalidCallee);
+        };
+        let name = callee.name.as_ref();
+        let function = ctx
+            .functions
+            .get(name)
+            .ok_or_else(|| EvalError::UnknownFunction(name.to_string()))?;
+        let arguments = self
+            .0
+            .arguments
+            .iter()
+            .map(|argument| Expression(argument).evaluate(ctx).map(Cow::into_owned))
+            .collect::<Result<Vec<_>, _>>()?;
+        function(&arguments)
+            .map(Cow::Owned)
+            .map_err(|message| EvalError::Function {
+                name: name.to_string(),
+                message,
+            })
+    }
+}
+
+pub struct Unary<'a>(&'a UnaryExpression);
+
+impl<'a> Unary<'a> {
+    fn evaluate<'v>(&self, ctx: &Context<'v>) -> EvalResult<'v>
+    where
+        'a: 'v,
+    {
+        let argument = Expression(&self.0.argument).evaluate(ctx)?;
+        Ok(Cow::Owned(match self.0.operator {
+            UnaryOperator::Not => JsonValue::Bool(!is_truthy(&argument)),
+            UnaryOperator::Negate => number(-to_number(&argument)),
+            UnaryOperator::Plus => number(to_number(&argument)),
+        }))
+    }
+}
+
+pub struct Binary<'a>(&'a BinaryExpression);
+
+impl<'a> Binary<'a> {
+    fn evaluate<'v>(&self, ctx: &Context<'v>) -> EvalResult<'v>
+    where
+        'a: 'v,
+    {
+        let left = Expression(&self.0.left).evaluate(ctx)?;
+        match self.0.operator {
+            Operator::And => {
+                if is_truthy(&left) {
+                    Expression(&self.0.right).evaluate(ctx)
+                } else {
+                    Ok(left)
+                }
+            }
+            Operator::Or => {
+                if is_truthy(&left) {
+                    Ok(left)
+                } else {
+                    Expression(&self.0.right).evaluate(ctx)
+                }
+            }
+            _ => {
+                let right = Expression(&self.0.right).evaluate(ctx)?;
+                Ok(Cow::Owned(self.apply(&left, &right)))
+            }
+        }
+    }
+
+    fn apply(&self, left: &JsonValue, right: &JsonValue) -> JsonValue {
+        match self.0.operator {
+            Operator::Add => match (left, right) {
+                (JsonValue::String(_), _) | (_, JsonValue::String(_)) => {
+                    JsonValue::String(to_string(left) + &to_string(right))
+                }
+                _ => number(to_number(left) + to_number(right)),
+            },
+            Operator::Sub => number(to_number(left) - to_number(right)),
+            Operator::Mul => number(to_number(left) * to_number(right)),
+            Operator::Div => number(to_number(left) / to_number(right)),
+            Operator::Mod => number(to_number(left) % to_number(right)),
+            _ => JsonValue::Bool(self.compare(left, right)),
+        }
+    }
+
+    fn compare(&self, left: &JsonValue, right: &JsonValue) -> bool {
+        match self.0.operator {
+            Operator::Equal => loose_equals(left, right),
+            Operator::NotEqual => !loose_equals(left, right),
+            Operator::StrictEqual => strict_equals(left, right),
+            Operator::StrictNotEqual => !strict_equals(left, right),
+            Operator::Greater => {
+                if let Some((l, r)) = left.as_number().zip(right.as_number()) {
+                    return l.as_f64() > r.as_f64();
+                } else if let Some((l, r)) = left.as_str().zip(right.as_str()) {
+                    return l > r;
+                } else if let Some((l, r)) = left.as_bool().zip(right.as_bool()) {
+                    return l & !r;
+                }
+                false
+            }
+            Operator::GreaterOrEqual => {
+                if let Some((l, r)) = left.as_number().zip(right.as_number()) {
+                    return l.as_f64() >= r.as_f64();
+                } else if let Some((l, r)) = left.as_str().zip(right.as_str()) {
+                    return l >= r;
+                } else if let Some((l, r)) = left.as_bool().zip(right.as_bool()) {
+                    return l >= r;
+                }
+                false
+            }
+            Operator::Lower => {
+                if let Some((l, r)) = left.as_number().zip(right.as_number()) {
+                    return l.as_f64() < r.as_f64();
+                } else if let Some((l, r)) = left.as_str().zip(right.as_str()) {
+                    return l < r;
+                } else if let Some((l, r)) = left.as_bool().zip(right.as_bool()) {
+                    return !l & r;
+                }
+                false
+            }
+            Operator::LowerOrEqual => {
+                if let Some((l, r)) = left.as_number().zip(right.as_number()) {
+                    return l.as_f64() <= r.as_f64();
+                } else if let Some((l, r)) = left.as_str().zip(right.as_str()) {
+                    return l <= r;
+                } else if let Some((l, r)) = left.as_bool().zip(right.as_bool()) {
+                    return l <= r;
+                }
+                false
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+pub(crate) fn strict_equals(left: &JsonValue, right: &JsonValue) -> bool {
+    match left.as_number().zip(right.as_number()) {
+        Some((l, r)) => l.as_f64() == r.as_f64(),
+        None => left == right,
+    }
+}
+
+fn loose_equals(left: &JsonValue, right: &JsonValue) -> bool {
+    match (left, right) {
+        (JsonValue::Null, _) | (_, JsonValue::Null) => left.is_null() && right.is_null(),
+        (JsonValue::Bool(_) | JsonValue::Number(_) | JsonValue::String(_), JsonValue::Bool(_))
+        | (JsonValue::Bool(_), JsonValue::Number(_) | JsonValue::String(_))
+        | (JsonValue::Number(_), JsonValue::String(_))
+        | (JsonValue::String(_), JsonValue::Number(_)) => to_number(left) == to_number(right),
+        _ => strict_equals(left, right),
+    }
+}
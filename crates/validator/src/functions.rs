@@ -0,0 +1,262 @@
+use std::{collections::HashMap, sync::Arc};
+use wfrs_model::json::{JsonNumber, JsonValue};
+
+use crate::eval::{strict_equals, to_number, to_string};
+
+pub type Function = Arc<dyn Fn(&[JsonValue]) -> Result<JsonValue, String> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Function>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+        }
+    }
+
+    pub fn register<F>(&mut self, name: impl Into<String>, function: F)
+    where
+        F: Fn(&[JsonValue]) -> Result<JsonValue, String> + Send + Sync + 'static,
+    {
+        self.functions.insert(name.into(), Arc::new(function));
+    }
+
+    pub fn with<F>(mut self, name: impl Into<String>, function: F) -> Self
+    where
+        F: Fn(&[JsonValue]) -> Result<JsonValue, String> + Send + Sync + 'static,
+    {
+        self.register(name, function);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        let registry = Self::new()
+            .with("len", len)
+            .with("length", len)
+            .with("contains", contains)
+            .with("lower", lower)
+            .with("upper", upper)
+            .with("startsWith", starts_with)
+            .with("endsWith", ends_with)
+            .with("min", min)
+            .with("max", max)
+            .with("date", date)
+            .with("isEmpty", is_empty);
+        #[cfg(not(target_arch = "wasm32"))]
+        let registry = registry.with("now", now);
+        registry
+    }
+}
+
+fn number(value: f64) -> JsonValue {
+    JsonValue::Number(JsonNumber::from(value))
+}
+
+fn arity(name: &str, args: &[JsonValue], expected: usize) -> Result<(), String> {
+    if args.len() != expected {
+        return Err(format!(
+            "{name}() expects {expected} argument(s), got {}",
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+fn len(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("len", args, 1)?;
+    let len = match &args[0] {
+        JsonValue::Null => 0,
+        JsonValue::String(s) => s.chars().count(),
+        JsonValue::Array(a) => a.len(),
+        JsonValue::Object(o) => o.len(),
+        value => return Err(format!("len() is not defined for {value:?}")),
+    };
+    Ok(number(len as f64))
+}
+
+fn contains(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("contains", args, 2)?;
+    let found = match &args[0] {
+        JsonValue::Null => false,
+        JsonValue::String(s) => s.contains(&to_string(&args[1])),
+        JsonValue::Array(a) => a.iter().any(|item| strict_equals(item, &args[1])),
+        JsonValue::Object(o) => o.contains_key(&to_string(&args[1])),
+        value => return Err(format!("contains() is not defined for {value:?}")),
+    };
+    Ok(JsonValue::Bool(found))
+}
+
+fn lower(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("lower", args, 1)?;
+    Ok(JsonValue::String(to_string(&args[0]).to_lowercase()))
+}
+
+fn upper(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("upper", args, 1)?;
+    Ok(JsonValue::String(to_string(&args[0]).to_uppercase()))
+}
+
+fn starts_with(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("startsWith", args, 2)?;
+    Ok(JsonValue::Bool(
+        to_string(&args[0]).starts_with(&to_string(&args[1])),
+    ))
+}
+
+fn ends_with(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("endsWith", args, 2)?;
+    Ok(JsonValue::Bool(
+        to_string(&args[0]).ends_with(&to_string(&args[1])),
+    ))
+}
+
+fn numbers(name: &str, args: &[JsonValue]) -> Result<Vec<f64>, String> {
+    let numbers: Vec<f64> = match args {
+        [JsonValue::Array(a)] => a.iter().map(to_number).collect(),
+        args => args.iter().map(to_number).collect(),
+    };
+    if numbers.is_empty() {
+        return Err(format!("{name}() expects at least 1 argument(s), got 0"));
+    }
+    Ok(numbers)
+}
+
+fn min(args: &[JsonValue]) -> Result<JsonValue, String> {
+    Ok(number(
+        numbers("min", args)?
+            .into_iter()
+            .fold(f64::INFINITY, f64::min),
+    ))
+}
+
+fn max(args: &[JsonValue]) -> Result<JsonValue, String> {
+    Ok(number(
+        numbers("max", args)?
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max),
+    ))
+}
+
+fn is_empty(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("isEmpty", args, 1)?;
+    Ok(JsonValue::Bool(match &args[0] {
+        JsonValue::Null => true,
+        JsonValue::String(s) => s.is_empty(),
+        JsonValue::Array(a) => a.is_empty(),
+        JsonValue::Object(o) => o.is_empty(),
+        JsonValue::Bool(_) | JsonValue::Number(_) => false,
+    }))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("now", args, 0)?;
+    let elapsed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|err| err.to_string())?;
+    Ok(number(elapsed.as_millis() as f64))
+}
+
+fn date(args: &[JsonValue]) -> Result<JsonValue, String> {
+    arity("date", args, 1)?;
+    match &args[0] {
+        JsonValue::Number(n) => Ok(number(n.as_f64())),
+        JsonValue::String(s) => parse_date(s)
+            .map(number)
+            .ok_or_else(|| format!("date() unable to parse '{s}'")),
+        value => Err(format!("date() is not defined for {value:?}")),
+    }
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn parse_date(s: &str) -> Option<f64> {
+    fn int(s: &str, range: std::ops::Range<usize>) -> Option<i64> {
+        let part = s.get(range)?;
+        if !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        part.parse().ok()
+    }
+    let s = s.trim();
+    let (year, month, day) = (int(s, 0..4)?, int(s, 5..7)?, int(s, 8..10)?);
+    if s.get(4..5)? != "-" || s.get(7..8)? != "-" || !(1..=12).contains(&month) {
+        return None;
+    }
+    // dates past the end of the month would silently roll over into the next one
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=days).contains(&day) {
+        return None;
+    }
+    let mut millis = days_from_civil(year, month, day) as f64 * 86_400_000.0;
+    let rest = &s[10..];
+    if rest.is_empty() {
+        return Some(millis);
+    }
+    let time = rest.strip_prefix(['T', ' '])?;
+    let (hour, minute) = (int(time, 0..2)?, int(time, 3..5)?);
+    if time.get(2..3)? != ":" || hour >= 24 || minute >= 60 {
+        return None;
+    }
+    let mut rest = &time[5..];
+    let mut seconds = 0.0;
+    if let Some(tail) = rest.strip_prefix(':') {
+        let end = tail
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(tail.len());
+        seconds = tail[..end].parse().ok()?;
+        if !(0.0..60.0).contains(&seconds) {
+            return None;
+        }
+        rest = &tail[end..];
+    }
+    millis += (hour * 3_600_000 + minute * 60_000) as f64 + seconds * 1000.0;
+    match rest {
+        "" | "Z" => Some(millis),
+        offset => {
+            let sign = match offset.get(0..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            // only `+HH`, `+HHMM` and `+HH:MM` are offsets
+            let hours = int(offset, 1..3)?;
+            let minutes = match offset.len() {
+                3 => 0,
+                5 => int(offset, 3..5)?,
+                6 if offset.get(3..4)? == ":" => int(offset, 4..6)?,
+                _ => return None,
+            };
+            if hours >= 24 || minutes >= 60 {
+                return None;
+            }
+            Some(millis - (sign * (hours * 3_600_000 + minutes * 60_000)) as f64)
+        }
+    }
+}
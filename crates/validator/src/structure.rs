@@ -0,0 +1,304 @@
+use std::{collections::VecDeque, sync::Arc};
+use wfrs_model::{Task, TaskDef, WorkflowDefinition};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+
+pub fn validate(definition: &WorkflowDefinition) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    Structure {
+        definition,
+        diagnostics: &mut diagnostics,
+    }
+    .validate();
+    diagnostics
+}
+
+struct Structure<'d> {
+    definition: &'d WorkflowDefinition,
+    diagnostics: &'d mut Vec<Diagnostic>,
+}
+
+impl<'d> Structure<'d> {
+    fn validate(&mut self) {
+        self.check_references();
+        self.check_start_events();
+        self.check_defaults();
+        let graph = self.graph();
+        self.check_reachability(&graph);
+        self.check_cycles(&graph);
+        for task in self.definition.tasks.iter() {
+            if let Some(child) = self.definition.sub_process(task.id) {
+                self.diagnostics.extend(validate(child));
+            }
+        }
+    }
+
+    fn task_id(&self, task: i32) -> Arc<str> {
+        self.definition
+            .task_ids
+            .get(task as usize)
+            .cloned()
+            .unwrap_or_else(|| Arc::from(task.to_string()))
+    }
+
+    fn flow_id(&self, flow: i32) -> Arc<str> {
+        self.definition
+            .flow_ids
+            .get(flow as usize)
+            .cloned()
+            .unwrap_or_else(|| Arc::from(flow.to_string()))
+    }
+
+    fn is_task(&self, task: i32) -> bool {
+        task >= 0 && (task as usize) < self.definition.tasks.len()
+    }
+
+    fn is_flow(&self, flow: i32) -> bool {
+        flow >= 0 && (flow as usize) < self.definition.flows.len()
+    }
+
+    fn error(&mut self, kind: DiagnosticKind, element: Arc<str>, message: String) {
+        self.diagnostics
+            .push(Diagnostic::error(kind, element, message));
+    }
+
+    fn check_references(&mut self) {
+        for flow in self.definition.flows.iter() {
+            let element = self.flow_id(flow.id);
+            if !self.is_task(flow.source_ref) {
+                self.error(
+                    DiagnosticKind::UnresolvedReference,
+                    element.clone(),
+                    "sequence flow has an unresolved sourceRef".to_string(),
+                );
+            }
+            if !self.is_task(flow.target_ref) {
+                self.error(
+                    DiagnosticKind::UnresolvedReference,
+                    element,
+                    "sequence flow has an unresolved targetRef".to_string(),
+                );
+            }
+        }
+        for task in self.definition.tasks.iter() {
+            let unresolved = task
+                .incoming()
+                .iter()
+                .chain(task.outgoing().iter())
+                .filter(|flow| !self.is_flow(**flow))
+                .count();
+            if unresolved > 0 {
+                self.error(
+                    DiagnosticKind::UnresolvedReference,
+                    self.task_id(task.id),
+                    format!("{unresolved} incoming or outgoing flow(s) could not be resolved"),
+                );
+            }
+        }
+    }
+
+    fn check_start_events(&mut self) {
+        let start_events = self
+            .definition
+            .tasks
+            .iter()
+            .filter(|task| matches!(task.def, TaskDef::StartEvent(_)))
+            .count();
+        let element = self.definition.id.clone();
+        match start_events {
+            0 => self.error(
+                DiagnosticKind::MissingStartEvent,
+                element,
+                "process has no start event".to_string(),
+            ),
+            1 => (),
+            n => self.error(
+                DiagnosticKind::MultipleStartEvents,
+                element,
+                format!("process has {n} start events"),
+            ),
+        }
+    }
+
+    fn check_defaults(&mut self) {
+        for task in self.definition.tasks.iter() {
+            let (default, outgoing) = match &task.def {
+                TaskDef::ExclusiveGateway(ev) => (ev.default, &ev.outgoing),
+                TaskDef::InclusiveGateway(ev) => (ev.default, &ev.outgoing),
+                _ => continue,
+            };
+            if default == -1 && outgoing.is_empty() {
+                continue;
+            }
+            if !outgoing.contains(&default) {
+                let message = if self.is_flow(default) {
+                    format!(
+                        "default flow '{}' is not an outgoing flow of the gateway",
+                        self.flow_id(default)
+                    )
+                } else {
+                    "default flow could not be resolved".to_string()
+                };
+                self.error(
+                    DiagnosticKind::InvalidDefault,
+                    self.task_id(task.id),
+                    message,
+                );
+            }
+        }
+    }
+
+    fn graph(&self) -> Vec<Vec<usize>> {
+        self.definition
+            .tasks
+            .iter()
+            .map(|task| {
+                task.outgoing()
+                    .iter()
+                    .filter_map(|flow| self.definition.flows.get(*flow as usize))
+                    .filter(|flow| self.is_task(flow.target_ref))
+                    .map(|flow| flow.target_ref as usize)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn check_reachability(&mut self, graph: &[Vec<usize>]) {
+        let tasks = &self.definition.tasks;
+        let starts = tasks
+            .iter()
+            .filter(|task| matches!(task.def, TaskDef::StartEvent(_)))
+            .map(|task| task.id as usize);
+        let reachable = search(graph, starts);
+        let mut reverse = vec![Vec::new(); graph.len()];
+        for (source, targets) in graph.iter().enumerate() {
+            for target in targets {
+                reverse[*target].push(source);
+            }
+        }
+        let ends = tasks
+            .iter()
+            .filter(|task| matches!(task.def, TaskDef::EndEvent(_)))
+            .map(|task| task.id as usize);
+        let finishing = search(&reverse, ends);
+        for task in tasks.iter() {
+            let index = task.id as usize;
+            if !reachable[index] {
+                self.diagnostics.push(Diagnostic::warning(
+                    DiagnosticKind::UnreachableTask,
+                    self.task_id(task.id),
+                    "task is not reachable from a start event".to_string(),
+                ));
+            } else if !finishing[index] {
+                self.error(
+                    DiagnosticKind::DeadEnd,
+                    self.task_id(task.id),
+                    "no end event can be reached from this task".to_string(),
+                );
+            }
+        }
+    }
+
+    fn check_cycles(&mut self, graph: &[Vec<usize>]) {
+        for component in strongly_connected(graph) {
+            let cyclic = component.len() > 1 || graph[component[0]].contains(&component[0]);
+            if !cyclic {
+                continue;
+            }
+            let waits = component
+                .iter()
+                .filter_map(|task| self.definition.tasks.get(*task))
+                .any(is_wait_state);
+            if !waits {
+                let mut tasks: Vec<_> = component
+                    .iter()
+                    .map(|task| self.task_id(*task as i32).to_string())
+                    .collect();
+                tasks.sort();
+                self.error(
+                    DiagnosticKind::InfiniteLoop,
+                    self.task_id(component[0] as i32),
+                    format!("cycle without a user task: {}", tasks.join(", ")),
+                );
+            }
+        }
+    }
+}
+
+fn is_wait_state(task: &Task) -> bool {
+    matches!(
+        task.def,
+        TaskDef::UserTask(_) | TaskDef::SubProcess(_) | TaskDef::CallActivity(_)
+    )
+}
+
+fn search(graph: &[Vec<usize>], starts: impl Iterator<Item = usize>) -> Vec<bool> {
+    let mut visited = vec![false; graph.len()];
+    let mut queue: VecDeque<usize> = starts.filter(|start| *start < graph.len()).collect();
+    while let Some(node) = queue.pop_front() {
+        if std::mem::replace(&mut visited[node], true) {
+            continue;
+        }
+        queue.extend(graph[node].iter().filter(|next| !visited[**next]));
+    }
+    visited
+}
+
+// iterative tarjan, a long linear process would overflow the stack when recursing
+fn strongly_connected(graph: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut index = 0;
+    let mut indices: Vec<Option<usize>> = vec![None; graph.len()];
+    let mut lowlink = vec![0; graph.len()];
+    let mut on_stack = vec![false; graph.len()];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    for root in 0..graph.len() {
+        if indices[root].is_some() {
+            continue;
+        }
+        let mut calls = vec![(root, 0usize)];
+        while let Some((node, next)) = calls.pop() {
+            if next == 0 {
+                indices[node] = Some(index);
+                lowlink[node] = index;
+                index += 1;
+                stack.push(node);
+                on_stack[node] = true;
+            } else {
+                // returning from the successor visited last
+                let child = graph[node][next - 1];
+                lowlink[node] = lowlink[node].min(lowlink[child]);
+            }
+            let mut descended = false;
+            for (offset, child) in graph[node].iter().copied().enumerate().skip(next) {
+                match indices[child] {
+                    None => {
+                        calls.push((node, offset + 1));
+                        calls.push((child, 0));
+                        descended = true;
+                        break;
+                    }
+                    Some(child_index) if on_stack[child] => {
+                        lowlink[node] = lowlink[node].min(child_index);
+                    }
+                    _ => (),
+                }
+            }
+            if descended {
+                continue;
+            }
+            if Some(lowlink[node]) == indices[node] {
+                let mut component = Vec::new();
+                while let Some(top) = stack.pop() {
+                    on_stack[top] = false;
+                    component.push(top);
+                    if top == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
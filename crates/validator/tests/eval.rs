@@ -0,0 +1,184 @@
+use wfrs_model::jsep::parse;
+use wfrs_model::json::JsonValue;
+use wfrs_model::ConditionExpression;
+use wfrs_validator::{
+    is_truthy, to_number, Condition, Context, EvalError, Expression, FunctionRegistry,
+};
+
+fn variables() -> JsonValue {
+    let mut variables = JsonValue::map();
+    variables.set_path("form.amount", JsonValue::Number(150.0.into()));
+    variables.set_path("form.name", JsonValue::String("Ada".to_string()));
+    variables.set_path(
+        "form.tags",
+        JsonValue::Array(vec![
+            JsonValue::String("a".to_string()),
+            JsonValue::String("b".to_string()),
+        ]),
+    );
+    variables.set_path("$vars.region", JsonValue::String("eu".to_string()));
+    variables.set_path("$vars.field", JsonValue::String("name".to_string()));
+    variables
+}
+
+fn eval(source: &str) -> Result<JsonValue, EvalError> {
+    let node = parse(source).unwrap();
+    let variables = variables();
+    let functions = FunctionRegistry::default();
+    let ctx = Context::new(&variables, &functions);
+    Expression(&node)
+        .evaluate(&ctx)
+        .map(|value| value.into_owned())
+}
+
+fn number(source: &str) -> f64 {
+    to_number(&eval(source).unwrap())
+}
+
+fn boolean(source: &str) -> bool {
+    match eval(source).unwrap() {
+        JsonValue::Bool(value) => value,
+        value => panic!("`{source}` evaluated to {value:?}"),
+    }
+}
+
+fn string(source: &str) -> String {
+    match eval(source).unwrap() {
+        JsonValue::String(value) => value,
+        value => panic!("`{source}` evaluated to {value:?}"),
+    }
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(number("1 + 2 * 3"), 7.0);
+    assert_eq!(number("(1 + 2) * 3"), 9.0);
+    assert_eq!(number("10 / 4"), 2.5);
+    assert_eq!(number("7 % 4"), 3.0);
+    assert_eq!(number("5 - '2'"), 3.0);
+    assert_eq!(number("-'3'"), -3.0);
+    assert_eq!(number("+true"), 1.0);
+    assert_eq!(string("'a' + 1"), "a1");
+    assert!(number("1 * 'x'").is_nan());
+}
+
+#[test]
+fn comparison() {
+    assert!(boolean("2 > 1"));
+    assert!(boolean("'b' > 'a'"));
+    assert!(boolean("2 >= 2"));
+    assert!(boolean("1 < 2"));
+    assert!(!boolean("2 <= 1"));
+    assert!(!boolean("'1' < 2"));
+    assert!(boolean("false < true"));
+}
+
+#[test]
+fn equality() {
+    assert!(boolean("1 == '1'"));
+    assert!(!boolean("1 === '1'"));
+    assert!(boolean("true == 1"));
+    assert!(!boolean("true === 1"));
+    assert!(boolean("null == null"));
+    assert!(!boolean("null == 0"));
+    assert!(boolean("1 != '2'"));
+    assert!(!boolean("1 !== 1.0"));
+    assert!(boolean("$steps.form.name === 'Ada'"));
+}
+
+#[test]
+fn logical() {
+    assert_eq!(string("0 || 'x'"), "x");
+    assert_eq!(string("'' && 1"), "");
+    assert!(boolean("!0"));
+    assert!(!boolean("!'text'"));
+    // the right hand side is not evaluated once the result is known
+    assert!(boolean("true || $missing"));
+    assert!(!boolean("false && $missing"));
+    assert_eq!(string("$steps.form.amount > 100 ? 'big' : 'small'"), "big");
+}
+
+#[test]
+fn member_access() {
+    assert_eq!(string("$steps.form.tags[1]"), "b");
+    assert_eq!(string("$steps.form['name']"), "Ada");
+    assert_eq!(string("$steps.form.name[0]"), "A");
+    assert_eq!(number("$steps.form.tags.length"), 2.0);
+    assert_eq!(string("$vars.region"), "eu");
+    assert_eq!(eval("$steps.form.missing"), Ok(JsonValue::Null));
+    assert_eq!(eval("$steps.missing?.field"), Ok(JsonValue::Null));
+    assert_eq!(eval("$steps.missing?.field.deeper"), Ok(JsonValue::Null));
+    assert_eq!(
+        eval("$steps.missing.field"),
+        Err(EvalError::NullAccess("field".to_string()))
+    );
+    assert_eq!(
+        eval("form.amount"),
+        Err(EvalError::UnknownIdentifier("form".to_string()))
+    );
+}
+
+#[test]
+fn computed_members() {
+    assert_eq!(string("$steps.form.tags[$steps.form.tags.length - 1]"), "b");
+    assert_eq!(string("$steps['form'][$vars.field]"), "Ada");
+    assert_eq!(string("$steps.form.tags['0']"), "a");
+    assert_eq!(eval("$steps.form.tags[2]"), Ok(JsonValue::Null));
+    assert_eq!(eval("$steps.form.tags[-1]"), Ok(JsonValue::Null));
+    assert_eq!(eval("$steps.form.tags[0.5]"), Ok(JsonValue::Null));
+    assert_eq!(eval("$steps.form.name[9]"), Ok(JsonValue::Null));
+    assert_eq!(eval("$steps.form.amount.length"), Ok(JsonValue::Null));
+    assert_eq!(eval("$steps.missing?.[0]"), Ok(JsonValue::Null));
+    assert_eq!(
+        eval("$steps.missing[0]"),
+        Err(EvalError::NullAccess("0".to_string()))
+    );
+}
+
+#[test]
+fn calls() {
+    assert_eq!(number("len($steps.form.tags)"), 2.0);
+    assert_eq!(string("upper($steps.form.name)"), "ADA");
+    assert!(boolean("contains($steps.form.tags, 'a')"));
+    assert_eq!(
+        eval("shout($steps.form.name)"),
+        Err(EvalError::UnknownFunction("shout".to_string()))
+    );
+    assert_eq!(eval("$steps.form.name()"), Err(EvalError::InvalidCallee));
+    assert!(matches!(
+        eval("len(1)"),
+        Err(EvalError::Function { name, .. }) if name == "len"
+    ));
+}
+
+#[test]
+fn truthiness() {
+    assert!(!is_truthy(&JsonValue::Null));
+    assert!(!is_truthy(&JsonValue::Number(0.0.into())));
+    assert!(!is_truthy(&JsonValue::Number(f64::NAN.into())));
+    assert!(!is_truthy(&JsonValue::String(String::new())));
+    assert!(is_truthy(&JsonValue::String("0".to_string())));
+    assert!(is_truthy(&JsonValue::Array(vec![])));
+    assert!(is_truthy(&JsonValue::map()));
+
+    assert_eq!(to_number(&JsonValue::Null), 0.0);
+    assert_eq!(to_number(&JsonValue::Bool(true)), 1.0);
+    assert_eq!(to_number(&JsonValue::String(" 12 ".to_string())), 12.0);
+    assert_eq!(to_number(&JsonValue::String(String::new())), 0.0);
+    assert!(to_number(&JsonValue::String("x".to_string())).is_nan());
+    assert!(to_number(&JsonValue::Array(vec![])).is_nan());
+}
+
+#[test]
+fn failing_condition() {
+    let condition = ConditionExpression::Jsep(parse("$steps.missing.approved == true").unwrap());
+    let variables = variables();
+    let functions = FunctionRegistry::default();
+    let ctx = Context::new(&variables, &functions);
+    assert_eq!(
+        Condition(&condition).evaluate(&ctx),
+        Err(EvalError::NullAccess("approved".to_string()))
+    );
+    // an error counts as a condition that does not hold
+    assert!(!Condition(&condition).validate(&ctx));
+}
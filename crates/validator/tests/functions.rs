@@ -0,0 +1,103 @@
+use wfrs_model::json::JsonValue;
+use wfrs_validator::{to_number, FunctionRegistry};
+
+fn call(name: &str, args: &[JsonValue]) -> Result<JsonValue, String> {
+    let functions = FunctionRegistry::default();
+    let function = functions.get(name).unwrap();
+    function(args)
+}
+
+fn date(value: &str) -> Option<f64> {
+    call("date", &[JsonValue::String(value.to_string())])
+        .ok()
+        .map(|value| to_number(&value))
+}
+
+fn numbers(values: &[f64]) -> Vec<JsonValue> {
+    values
+        .iter()
+        .map(|value| JsonValue::Number((*value).into()))
+        .collect()
+}
+
+#[test]
+fn dates() {
+    let midnight = 1_704_067_200_000.0;
+    assert_eq!(date("2024-01-01"), Some(midnight));
+    assert_eq!(date("2024-01-01T00:00Z"), Some(midnight));
+    assert_eq!(date("2024-01-01 12:30:15.5"), Some(midnight + 45_015_500.0));
+    assert_eq!(date("2024-01-01T05:00+05"), Some(midnight));
+    assert_eq!(date("2024-01-01T05:30+0530"), Some(midnight));
+    assert_eq!(date("2024-01-01T05:30+05:30"), Some(midnight));
+    assert_eq!(date("2023-12-31T22:00-02:00"), Some(midnight));
+    assert_eq!(date("2024-01-01T00:00+5"), None);
+    assert_eq!(date("2024-01-01T00:00+053"), None);
+    assert_eq!(date("2024-01-01T00:00+05:3"), None);
+    assert_eq!(date("2024-01-01T00:00+05-30"), None);
+    assert_eq!(date("2024-13-01"), None);
+    assert_eq!(date("2024-02-29"), Some(midnight + 59.0 * 86_400_000.0));
+    assert_eq!(date("2023-02-29"), None);
+    assert_eq!(date("2024-02-31T12:00"), None);
+    assert_eq!(date("2024-04-31"), None);
+    assert_eq!(date("2024-01-00"), None);
+    assert_eq!(date("2024-01-01T12x30"), None);
+    assert_eq!(date("2024-01-01T24:00"), None);
+    assert_eq!(date("2024-01-01T25:99"), None);
+    assert_eq!(date("2024-01-01T12:60"), None);
+    assert_eq!(date("2024-01-01T12:30:60"), None);
+    assert_eq!(date("2024-01-01T12:30+24:00"), None);
+    assert_eq!(date("2024-01-01T12:30+05:60"), None);
+    assert_eq!(date("01.01.2024"), None);
+    assert_eq!(
+        call("date", &numbers(&[midnight])).map(|value| to_number(&value)),
+        Ok(midnight)
+    );
+}
+
+#[test]
+fn min_max() {
+    let min = |args: &[JsonValue]| call("min", args).map(|value| to_number(&value));
+    let max = |args: &[JsonValue]| call("max", args).map(|value| to_number(&value));
+    assert_eq!(min(&numbers(&[3.0, 1.0, 2.0])), Ok(1.0));
+    assert_eq!(max(&numbers(&[3.0, 1.0, 2.0])), Ok(3.0));
+    assert_eq!(max(&[JsonValue::Array(numbers(&[4.0, 9.0]))]), Ok(9.0));
+    assert!(min(&[]).is_err());
+    assert!(max(&[]).is_err());
+    assert!(max(&[JsonValue::Array(vec![])]).is_err());
+}
+
+#[test]
+fn arity() {
+    assert!(call("len", &[]).is_err());
+    assert!(call("contains", &[JsonValue::Null]).is_err());
+    assert!(call("date", &[]).is_err());
+}
+
+#[test]
+fn strings() {
+    let text = |value: &str| JsonValue::String(value.to_string());
+    assert_eq!(
+        call("len", &[text("héllo")]),
+        Ok(JsonValue::Number(5.0.into()))
+    );
+    assert_eq!(call("lower", &[text("ABC")]), Ok(text("abc")));
+    assert_eq!(
+        call("startsWith", &[text("invoice-1"), text("invoice")]),
+        Ok(JsonValue::Bool(true))
+    );
+    assert_eq!(
+        call("endsWith", &[text("invoice-1"), text("2")]),
+        Ok(JsonValue::Bool(false))
+    );
+    assert_eq!(call("isEmpty", &[text("")]), Ok(JsonValue::Bool(true)));
+    assert_eq!(
+        call(
+            "contains",
+            &[
+                JsonValue::Array(numbers(&[1.0])),
+                JsonValue::Number(1.0.into())
+            ]
+        ),
+        Ok(JsonValue::Bool(true))
+    );
+}
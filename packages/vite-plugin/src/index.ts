@@ -104,7 +104,13 @@ export function renderAssetUrlInJS(
 }
 
 const PLUGIN_NAME = 'wfrs-vite-plugin';
-export default function wfrs(): any {
+
+export interface WfrsPluginOptions {
+    // skip unsupported bpmn elements with a warning instead of failing
+    lenient?: boolean;
+}
+
+export default function wfrs(options: WfrsPluginOptions = {}): any {
     let viteConfig: any | null = null;
     return {
         name: PLUGIN_NAME,
@@ -125,6 +131,7 @@ export default function wfrs(): any {
                 id.replace('.bpmn', '.wfrs'),
                 viteConfig,
                 this,
+                options.lenient ?? false,
             );
             return `export default ${JSON.stringify(url)}`;
         },
@@ -152,8 +159,9 @@ export async function fileToUrl(
     nextId: string,
     config: ResolvedConfig,
     ctx: any,
+    lenient = false,
 ): Promise<string> {
-    return await fileToBuiltUrl(id, nextId, config, ctx);
+    return await fileToBuiltUrl(id, nextId, config, ctx, lenient);
 }
 
 async function fileToBuiltUrl(
@@ -161,6 +169,7 @@ async function fileToBuiltUrl(
     _nextId: string,
     config: ResolvedConfig,
     _pluginContext: PluginContext,
+    lenient: boolean,
 ): Promise<string> {
     const cache = assetCache.get(config) ?? null;
     if (cache !== null) {
@@ -171,7 +180,7 @@ async function fileToBuiltUrl(
     }
     const file = cleanUrl(id);
     const fileContent = await fsp.readFile(file, 'utf-8');
-    const content = fromXML(fileContent);
+    const content = fromXML(fileContent, lenient);
 
     const mimeType = 'application/octet-stream';
     // base64 inlined as a string
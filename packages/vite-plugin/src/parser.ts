@@ -1,11 +1,30 @@
-import { init, parse } from '@wfrs/parser';
+import { init, parse, parseWithOptions, ParseOptions } from '@wfrs/parser';
 
 export interface ParseResult {
     errors: string[];
     result: Uint8Array;
 }
 
-export function fromXML(src: string): Uint8Array {
+export interface ParseIssue {
+    severity: 'warning' | 'error';
+    kind: string;
+    message: string;
+    element?: string;
+    id?: string;
+    line?: number;
+    column?: number;
+}
+
+export function fromXML(src: string, lenient = false): Uint8Array {
     init();
-    return parse(src);
+    if (!lenient) {
+        return parse(src);
+    }
+    const options = new ParseOptions();
+    options.lenient = true;
+    const output = parseWithOptions(src, options);
+    for (const warning of output.warnings as ParseIssue[]) {
+        console.warn(`[wfrs] ${warning.message}`);
+    }
+    return output.data;
 }
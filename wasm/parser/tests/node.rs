@@ -4,9 +4,23 @@ use wasm_bindgen_test::*;
 
 #[wasm_bindgen_test]
 fn pass() -> Result<(), String> {
-    let diagram = std::include_str!("./diagram.bpmn");
+    let diagram = std::include_str!("../../../crates/parser/tests/diagram.bpmn");
     parser::parse(diagram).map_err(|err| {
-        format!("{err}")
+        format!("{err:?}")
     })?;
     Ok(())
 }
+
+#[wasm_bindgen_test]
+fn bundle() -> Result<(), String> {
+    let diagram = std::include_str!("../../../crates/parser/tests/diagram.bpmn");
+    let definition = parser::parse(diagram).map_err(|err| format!("{err:?}"))?;
+    let definition =
+        wfrs_model::deserialize(&definition.to_vec()).map_err(|err| format!("{err:?}"))?;
+    let output = parser::parse_bundle(diagram, &parser::ParseOptions::new())
+        .map_err(|err| format!("{err:?}"))?;
+    let bundle = wfrs_model::deserialize_bundle(&output.data().to_vec())
+        .map_err(|err| format!("{err:?}"))?;
+    assert_eq!(bundle.get(&definition.id), Some(&definition));
+    Ok(())
+}
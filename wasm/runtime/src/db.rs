@@ -3,16 +3,10 @@ use js_sys::Uint8Array;
 use rexie::ObjectStore;
 use rexie::Rexie;
 use rexie::TransactionMode;
-use rkyv::de::deserializers::SharedDeserializeMapError;
-use rkyv::ser::serializers::AllocSerializer;
-use rkyv::ser::serializers::{
-    AllocScratchError, CompositeSerializerError, SharedSerializeMapError,
-};
-use rkyv::ser::Serializer;
-use rkyv::Deserialize;
 use wasm_bindgen::prelude::*;
 use wfrs_engine::state::State;
 use wfrs_engine::state::WorkflowState;
+use wfrs_engine::StateError;
 
 pub struct DbEntry {
     pub id: String,
@@ -46,24 +40,17 @@ impl IndexedDb {
     }
 }
 
-async fn serialize_entry(
-    entry: DbEntry,
-) -> Result<
-    Object,
-    CompositeSerializerError<std::convert::Infallible, AllocScratchError, SharedSerializeMapError>,
-> {
+async fn serialize_entry(entry: DbEntry) -> Object {
     let DbEntry { id, state, touched } = entry;
     let s = state.state().await;
-    let mut serializer = AllocSerializer::<0>::default();
-    serializer.serialize_value(&s.inner)?;
-    let result = serializer.into_serializer().into_inner();
+    let result = wfrs_engine::state::serialize(&s.inner);
     let buf = js_sys::Uint8Array::new_with_length(result.len() as u32);
     buf.copy_from(&result);
     let obj = js_sys::Object::new();
     js_sys::Reflect::set(&obj, &"id".into(), &id.into()).unwrap();
     js_sys::Reflect::set(&obj, &"data".into(), &buf.into()).unwrap();
     js_sys::Reflect::set(&obj, &"touched".into(), &touched.into()).unwrap();
-    Ok(obj)
+    obj
 }
 
 async fn store_entry(rexie: &Rexie, entry: JsValue) -> Result<(), rexie::Error> {
@@ -74,9 +61,8 @@ async fn store_entry(rexie: &Rexie, entry: JsValue) -> Result<(), rexie::Error>
     Ok(())
 }
 
-pub async fn deserialize_entry(data: &[u8]) -> Result<State, SharedDeserializeMapError> {
-    let archived = unsafe { rkyv::archived_root::<State>(data) };
-    archived.deserialize(&mut rkyv::de::deserializers::SharedDeserializeMap::default())
+pub async fn deserialize_entry(data: &[u8]) -> Result<State, StateError> {
+    wfrs_engine::state::deserialize(data)
 }
 
 pub async fn read_entry(rexie: &Rexie, id: &str) -> Result<Option<Uint8Array>, rexie::Error> {
@@ -100,7 +86,7 @@ pub async fn remove_entry(rexie: &Rexie, id: &str) -> Result<(), rexie::Error> {
 
 impl IndexedDb {
     pub async fn store(&self, entry: DbEntry) -> anyhow::Result<()> {
-        let entry: JsValue = serialize_entry(entry).await?.into();
+        let entry: JsValue = serialize_entry(entry).await.into();
         store_entry(&self.rexie, entry)
             .await
             .map_err(|e| anyhow::anyhow!("{e:#?}"))?;
@@ -1,23 +1,55 @@
 use crate::db::deserialize_entry;
 
 use wasm_bindgen::prelude::*;
-use wfrs_model::deserialize;
-use wfrs_model::WorkflowDefinition;
+use wfrs_model::{deserialize, deserialize_bundle};
+use wfrs_model::{WorkflowBundle, WorkflowDefinition};
 use wfrs_engine::Runtime;
 
+use std::sync::Arc;
+
 use crate::db::{load, store};
 use crate::db::{DbEntry, IndexedDb};
+use crate::functions::functions;
 use crate::instance::JsWorkflowInstance;
+use crate::store::Store;
 
 #[derive(Clone)]
 #[wasm_bindgen]
-pub struct JsWorkflowDefinition(&'static WorkflowDefinition);
+pub struct JsWorkflowDefinition(&'static WorkflowDefinition, Option<Arc<Store>>);
+
+pub(crate) fn create_all(data: &[u8]) -> Result<Vec<JsWorkflowDefinition>, String> {
+    let bundle = deserialize_bundle(data).map_err(|e| format!("{e:#?}"))?;
+    let bundle: &'static WorkflowBundle = Box::leak(Box::new(bundle));
+    Ok(bundle
+        .processes
+        .iter()
+        .map(|definition| JsWorkflowDefinition(definition, None))
+        .collect())
+}
 
 #[wasm_bindgen]
 pub fn create(data: &[u8]) -> Result<JsWorkflowDefinition, String> {
     let definition = deserialize(data).map_err(|e| format!("{e:#?}"))?;
-    let js_workflow = Box::new(definition);
-    Ok(JsWorkflowDefinition(Box::leak(js_workflow)))
+    Ok(JsWorkflowDefinition(Box::leak(Box::new(definition)), None))
+}
+
+impl JsWorkflowDefinition {
+    pub(crate) fn with_store(mut self, store: Arc<Store>) -> Self {
+        self.1 = Some(store);
+        self
+    }
+
+    pub(crate) fn definition(&self) -> &'static WorkflowDefinition {
+        self.0
+    }
+
+    fn runtime(&self, entity_id: &str) -> Runtime<'static> {
+        let runtime = Runtime::new(self.0, self.0.format_id(entity_id)).with_functions(functions());
+        match self.1.as_ref() {
+            Some(store) => runtime.with_resolver(store.clone()),
+            None => runtime,
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -28,13 +60,17 @@ impl JsWorkflowDefinition {
     }
 
     pub fn id(&self) -> String {
-        format!("{}:{}", self.0.id, self.0.version)
+        self.0.key()
     }
 
     pub fn version(&self) -> String {
         self.0.version.to_string()
     }
 
+    pub fn version_source(&self) -> String {
+        self.0.version_source.to_string()
+    }
+
     pub fn has_autostart(&self) -> bool {
         self.0
             .options
@@ -63,7 +99,7 @@ impl JsWorkflowDefinition {
         let state = deserialize_entry(state)
             .await
             .map_err(|err| format!("{err:#?}"))?;
-        let runtime = Runtime::new(self.0, self.0.format_id(&entity_id)).with_state(state);
+        let runtime = self.runtime(&entity_id).with_state(state);
         runtime
             .instance
             .set_remote_id(remote_id, remote_version)
@@ -80,9 +116,9 @@ impl JsWorkflowDefinition {
     }
 
     pub async fn start(&self, entity_id: String) -> Result<JsWorkflowInstance, String> {
-        let js_runtime = Box::new(Runtime::new(self.0, self.0.format_id(&entity_id)));
-        js_runtime.run().await;
-        js_runtime.simulate().await;
+        let js_runtime = Box::new(self.runtime(&entity_id));
+        js_runtime.run().await.map_err(|err| err.to_string())?;
+        js_runtime.simulate().await.map_err(|err| err.to_string())?;
         js_runtime.set_default_active_task().await;
         store(DbEntry::new(
             js_runtime.entity_id.clone(),
@@ -95,7 +131,7 @@ impl JsWorkflowDefinition {
     }
 
     pub async fn load(&self, entity_id: String) -> Result<JsWorkflowInstance, String> {
-        let mut js_runtime = Box::new(Runtime::new(self.0, self.0.format_id(&entity_id)));
+        let mut js_runtime = Box::new(self.runtime(&entity_id));
         let entry = load(&js_runtime.entity_id).await?;
         if let Some(entry) = entry {
             js_runtime.instance = entry.state;
@@ -115,5 +151,10 @@ impl JsWorkflowDefinition {
 
     pub fn print(&self) {
         log::info!("{:#?}", self.0);
+        for (flow, id) in self.0.flows.iter().zip(self.0.flow_ids.iter()) {
+            if let Some(condition) = &flow.condition_expression {
+                log::info!("{id}: {condition}");
+            }
+        }
     }
 }
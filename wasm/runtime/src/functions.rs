@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use wfrs_model::json::{JsonNumber, JsonValue};
+use wfrs_validator::FunctionRegistry;
+
+lazy_static::lazy_static! {
+    static ref FUNCTIONS: Arc<FunctionRegistry> =
+        Arc::new(FunctionRegistry::default().with("now", now));
+}
+
+fn now(args: &[JsonValue]) -> Result<JsonValue, String> {
+    if !args.is_empty() {
+        return Err(format!("now() expects 0 argument(s), got {}", args.len()));
+    }
+    Ok(JsonValue::Number(JsonNumber::from(js_sys::Date::now())))
+}
+
+pub(crate) fn functions() -> Arc<FunctionRegistry> {
+    FUNCTIONS.clone()
+}
@@ -2,13 +2,11 @@ use crate::db::deserialize_entry;
 use crate::db::remove;
 use crate::db::store;
 use crate::db::DbEntry;
-use crate::variables::JsRuntimeVariables;
+use crate::variables::{object_from_js, JsRuntimeVariables};
 use js_sys::Object;
 use log::info;
-use rkyv::ser::serializers::AllocSerializer;
-use rkyv::ser::Serializer;
+use std::sync::Arc;
 use wasm_bindgen::prelude::*;
-use wfrs_model::json::JsonValue;
 use wfrs_engine::Runtime;
 
 #[wasm_bindgen]
@@ -39,25 +37,22 @@ impl JsWorkflowInstance {
 impl JsWorkflowInstance {
     pub async fn print(&self) {
         let state = self.rt.instance.state().await;
-        info!("current_tasks: {:#?}", state.inner.current_tasks);
-        info!("current_flows: {:#?}", state.inner.current_flows);
+        info!("tokens: {:#?}", state.inner.tokens);
         info!("visited_tasks: {:#?}", state.inner.visited_tasks);
         info!("visited_flows: {:#?}", state.inner.visited_flows);
-        info!("pending_tasks: {:#?}", state.inner.pending_tasks);
         info!("maybe_future_tasks: {:#?}", state.inner.maybe_future_tasks);
         info!("maybe_future_flows: {:#?}", state.inner.maybe_future_flows);
         info!(
             "maybe_visited_tasks: {:#?}",
             state.inner.maybe_visited_tasks
         );
+        info!("maybe_joined_flows: {:#?}", state.inner.maybe_joined_flows);
         info!("variables: {:#?}", state.inner.variables);
     }
 
     pub async fn state(&self) -> js_sys::Uint8Array {
         let s = self.rt.instance.state().await;
-        let mut serializer = AllocSerializer::<0>::default();
-        serializer.serialize_value(&s.inner).unwrap();
-        let result = serializer.into_serializer().into_inner().to_vec();
+        let result = wfrs_engine::state::serialize(&s.inner);
         let buf = js_sys::Uint8Array::new_with_length(result.len() as u32);
         buf.copy_from(&result);
         buf
@@ -83,7 +78,15 @@ impl JsWorkflowInstance {
 
     pub async fn pending_tasks(&self) -> js_sys::Int32Array {
         let state = self.rt.instance.state().await;
-        unsafe { js_sys::Int32Array::view(&state.inner.pending_tasks) }
+        js_sys::Int32Array::from(state.inner.pending_tasks().as_slice())
+    }
+
+    pub async fn pending_tasks_in(&self, scope: Vec<i32>) -> js_sys::Int32Array {
+        let state = self.rt.instance.state().await;
+        match state.inner.scope(&scope) {
+            Some(state) => js_sys::Int32Array::from(state.pending_tasks().as_slice()),
+            None => js_sys::Int32Array::new_with_length(0),
+        }
     }
 
     pub async fn get_active(&self) -> i32 {
@@ -119,7 +122,7 @@ impl JsWorkflowInstance {
 
     pub async fn complete(&self, task_id: i32) -> Result<(), String> {
         self.rt.complete(task_id).await?;
-        self.rt.simulate().await;
+        self.rt.simulate().await.map_err(|err| err.to_string())?;
         let pending_tasks = self.pending_tasks().await;
         if let Some(active) = pending_tasks.at(0) {
             self.rt.instance.set_active(active).await;
@@ -132,10 +135,36 @@ impl JsWorkflowInstance {
         Ok(())
     }
 
+    pub async fn complete_in(&self, scope: Vec<i32>, task_id: i32) -> Result<(), String> {
+        self.rt.complete_in(&scope, task_id).await?;
+        self.rt.simulate().await.map_err(|err| err.to_string())?;
+        store(DbEntry::new(
+            self.rt.entity_id.clone(),
+            self.rt.instance.clone(),
+        ))
+        .await?;
+        Ok(())
+    }
+
     pub async fn navigate_to(&self, task_id: i32) -> Result<(), String> {
-        self.rt.navigate_to(task_id).await;
-        self.rt.run().await;
-        self.rt.simulate().await;
+        self.rt.navigate_to(task_id).await.map_err(|err| err.to_string())?;
+        self.rt.run().await.map_err(|err| err.to_string())?;
+        self.rt.simulate().await.map_err(|err| err.to_string())?;
+        store(DbEntry::new(
+            self.rt.entity_id.clone(),
+            self.rt.instance.clone(),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    pub async fn history_len(&self) -> usize {
+        self.rt.instance.state().await.inner.history.len()
+    }
+
+    pub async fn undo(&self, step: usize) -> Result<(), String> {
+        self.rt.undo(step).await.map_err(|err| err.to_string())?;
+        self.rt.set_default_active_task().await;
         store(DbEntry::new(
             self.rt.entity_id.clone(),
             self.rt.instance.clone(),
@@ -146,7 +175,7 @@ impl JsWorkflowInstance {
 
     pub async fn get_variables(&self, task_id: i32) -> JsValue {
         let state = self.rt.instance.state().await;
-        let is_current_task = state.inner.pending_tasks.contains(&task_id);
+        let is_current_task = state.inner.is_waiting(task_id);
         if is_current_task {
             let key = self.rt.definition.task_ids[task_id as usize].as_ref();
             if let Some(variables) = state
@@ -162,66 +191,31 @@ impl JsWorkflowInstance {
     }
 
     pub async fn set_variables(&self, task_id: i32, variables: Object) -> Result<(), JsValue> {
-        async {
-            let mut state = self.rt.instance.mut_state().await;
-            let is_current_task = state.inner.pending_tasks.contains(&task_id);
-            if is_current_task {
-                let key = self.rt.definition.task_ids[task_id as usize].as_ref();
-                if let Some(obj) = state.inner.variables.as_object_mut() {
-                    let current_variables = if !obj.contains_key(key) {
-                        obj.insert(key.to_string(), JsonValue::map());
-                        obj.get_mut(key).unwrap().as_object_mut()
-                    } else {
-                        obj.get_mut(key).unwrap().as_object_mut()
-                    }
-                    .unwrap();
-                    for js_key in js_sys::Reflect::own_keys(&variables)?.iter() {
-                        if js_key.is_string() {
-                            let key = js_key.as_string().unwrap();
-                            let value = js_sys::Reflect::get(&variables, &js_key)?;
-                            if value.has_type::<js_sys::JsString>() {
-                                current_variables
-                                    .insert(key, JsonValue::String(value.as_string().unwrap()));
-                            } else if value.has_type::<js_sys::Number>() {
-                                let f = value.as_f64().unwrap();
-                                if f.fract() == 0.0 {
-                                    if f.is_sign_positive() {
-                                        current_variables.insert(
-                                            key,
-                                            JsonValue::Number(
-                                                wfrs_model::json::JsonNumber::PosInt(unsafe {
-                                                    f.to_int_unchecked::<u64>()
-                                                }),
-                                            ),
-                                        );
-                                    } else {
-                                        current_variables.insert(
-                                            key,
-                                            JsonValue::Number(
-                                                wfrs_model::json::JsonNumber::NegInt(unsafe {
-                                                    f.to_int_unchecked::<i64>()
-                                                }),
-                                            ),
-                                        );
-                                    }
-                                } else {
-                                    current_variables.insert(
-                                        key,
-                                        JsonValue::Number(wfrs_model::json::JsonNumber::Float(f)),
-                                    );
-                                }
-                            } else if value.has_type::<js_sys::Boolean>() {
-                                current_variables
-                                    .insert(key, JsonValue::Bool(value.as_bool().unwrap()));
-                            }
-                        }
-                    }
-                }
-            }
-            Ok::<(), JsValue>(())
+        let is_current_task = self.rt.instance.state().await.inner.is_waiting(task_id);
+        if is_current_task {
+            self.rt
+                .set_variables(task_id, object_from_js(&variables)?)
+                .await?;
         }
+        self.rt.simulate().await.map_err(|err| err.to_string())?;
+        store(DbEntry::new(
+            self.rt.entity_id.clone(),
+            self.rt.instance.clone(),
+        ))
         .await?;
-        self.rt.simulate().await;
+        Ok(())
+    }
+
+    pub async fn set_variables_in(
+        &self,
+        scope: Vec<i32>,
+        task_id: i32,
+        variables: Object,
+    ) -> Result<(), JsValue> {
+        self.rt
+            .set_variables_in(&scope, task_id, object_from_js(&variables)?)
+            .await?;
+        self.rt.simulate().await.map_err(|err| err.to_string())?;
         store(DbEntry::new(
             self.rt.entity_id.clone(),
             self.rt.instance.clone(),
@@ -230,6 +224,28 @@ impl JsWorkflowInstance {
         Ok(())
     }
 
+    pub async fn get_process_variables(&self) -> JsValue {
+        JsRuntimeVariables(&self.rt.process_variables().await).into()
+    }
+
+    pub async fn set_process_variables(&self, variables: Object) -> Result<(), JsValue> {
+        self.rt
+            .set_process_variables(object_from_js(&variables)?)
+            .await?;
+        self.rt.simulate().await.map_err(|err| err.to_string())?;
+        store(DbEntry::new(
+            self.rt.entity_id.clone(),
+            self.rt.instance.clone(),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    pub fn set_env(&mut self, env: Object) -> Result<(), JsValue> {
+        self.rt.env = Arc::new(object_from_js(&env)?);
+        Ok(())
+    }
+
     pub async fn is_completed(&self) -> bool {
         self.rt.instance.state().await.inner.completed
     }
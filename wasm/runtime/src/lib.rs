@@ -2,6 +2,7 @@
 // mod client;
 mod db;
 mod definition;
+mod functions;
 mod instance;
 mod store;
 mod utils;
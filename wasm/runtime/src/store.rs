@@ -1,16 +1,79 @@
-use futures_locks::RwLock;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError, RwLock};
 use wasm_bindgen::prelude::*;
+use wfrs_engine::resolver::DefinitionResolver;
+use wfrs_model::WorkflowDefinition;
 
 // use crate::client::proto::WorkflowInfo;
-use crate::definition::JsWorkflowDefinition;
+use crate::definition::{create, create_all, JsWorkflowDefinition};
 // use crate::instance::JsWorkflowInstance;
 
+// the lock is never held across an await, so the engine resolving a called element
+// synchronously always sees a consistent registry instead of failing while `register` runs
 #[derive(Default)]
-struct Store {
+pub(crate) struct Store {
     // active: RwLock<Vec<ActiveWorkflow>>,
-    definitions: RwLock<HashMap<String, JsWorkflowDefinition>>,
+    definitions: RwLock<Definitions>,
+}
+
+#[derive(Default)]
+struct Definitions {
+    by_key: HashMap<String, JsWorkflowDefinition>,
+    // process id to the key of its latest registered version
+    latest: HashMap<String, String>,
+}
+
+// dotted numeric versions compare by number, anything else (e.g. content hashes) is
+// only ordered by registration
+fn is_newer(version: &str, current: &str) -> bool {
+    let numeric = |version: &str| {
+        version
+            .split('.')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()
+    };
+    match (numeric(version), numeric(current)) {
+        (Some(version), Some(current)) => version.cmp(&current) != Ordering::Less,
+        _ => true,
+    }
+}
+
+impl Definitions {
+    fn insert(&mut self, definition: JsWorkflowDefinition) {
+        let key = definition.id();
+        let process = definition.definition();
+        let newer = match self
+            .latest
+            .get(process.id.as_ref())
+            .and_then(|latest| self.by_key.get(latest))
+        {
+            Some(latest) => is_newer(&process.version, &latest.definition().version),
+            None => true,
+        };
+        if newer {
+            self.latest.insert(process.id.to_string(), key.clone());
+        }
+        self.by_key.insert(key, definition);
+    }
+}
+
+impl DefinitionResolver<'static> for Store {
+    fn resolve(&self, called_element: &str) -> Option<&'static WorkflowDefinition> {
+        let definitions = self
+            .definitions
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let key = definitions
+            .latest
+            .get(called_element)
+            .map(String::as_str)
+            .unwrap_or(called_element);
+        definitions
+            .by_key
+            .get(key)
+            .map(|definition| definition.definition())
+    }
 }
 
 // #[wasm_bindgen(js_name = ActiveWorkflow)]
@@ -34,12 +97,31 @@ impl WorkflowStore {
     }
 
     pub async fn register(&self, data: &[u8]) -> Result<JsWorkflowDefinition, String> {
-        let definiton = JsWorkflowDefinition::new(data)?;
-        self.inner
+        let definition = create(data)?.with_store(self.inner.clone());
+        self.insert(std::slice::from_ref(&definition));
+        Ok(definition)
+    }
+
+    #[wasm_bindgen(js_name = registerBundle)]
+    pub async fn register_bundle(&self, data: &[u8]) -> Result<js_sys::Array, String> {
+        let definitions: Vec<_> = create_all(data)?
+            .into_iter()
+            .map(|definition| definition.with_store(self.inner.clone()))
+            .collect();
+        self.insert(&definitions);
+        Ok(definitions.into_iter().map(JsValue::from).collect())
+    }
+}
+
+impl WorkflowStore {
+    fn insert(&self, definitions: &[JsWorkflowDefinition]) {
+        let mut registered = self
+            .inner
             .definitions
             .write()
-            .await
-            .insert(definiton.id(), definiton.clone());
-        Ok(definiton)
+            .unwrap_or_else(PoisonError::into_inner);
+        for definition in definitions {
+            registered.insert(definition.clone());
+        }
     }
 }
@@ -1,6 +1,6 @@
 use wasm_bindgen::prelude::*;
 
-use wfrs_model::json::JsonValue;
+use wfrs_model::json::{JsonNumber, JsonValue};
 
 pub struct JsRuntimeVariables<'a>(pub &'a JsonValue);
 
@@ -13,8 +13,8 @@ impl<'a> From<JsRuntimeVariables<'a>> for JsValue {
             JsonValue::String(v) => js_sys::JsString::from(v.as_str()).into(),
             JsonValue::Array(v) => {
                 let result = js_sys::Array::new_with_length(v.len() as u32);
-                for i in 0..v.len() {
-                    result.set(i as u32, JsRuntimeVariables(&v[i]).into());
+                for (i, item) in v.iter().enumerate() {
+                    result.set(i as u32, JsRuntimeVariables(item).into());
                 }
                 result.into()
             }
@@ -28,3 +28,45 @@ impl<'a> From<JsRuntimeVariables<'a>> for JsValue {
         }
     }
 }
+
+pub fn from_js(value: &JsValue) -> Result<Option<JsonValue>, JsValue> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(Some(JsonValue::Null));
+    }
+    if let Some(s) = value.as_string() {
+        return Ok(Some(JsonValue::String(s)));
+    }
+    if let Some(f) = value.as_f64() {
+        return Ok(Some(JsonValue::Number(JsonNumber::from(f))));
+    }
+    if let Some(b) = value.as_bool() {
+        return Ok(Some(JsonValue::Bool(b)));
+    }
+    if js_sys::Array::is_array(value) {
+        let mut items = Vec::new();
+        for item in js_sys::Array::from(value).iter() {
+            if let Some(item) = from_js(&item)? {
+                items.push(item);
+            }
+        }
+        return Ok(Some(JsonValue::Array(items)));
+    }
+    if value.is_object() {
+        return object_from_js(value).map(Some);
+    }
+    Ok(None)
+}
+
+pub fn object_from_js(value: &JsValue) -> Result<JsonValue, JsValue> {
+    let mut object = JsonValue::map();
+    if let Some(obj) = object.as_object_mut() {
+        for js_key in js_sys::Reflect::own_keys(value)?.iter() {
+            if let Some(key) = js_key.as_string() {
+                if let Some(value) = from_js(&js_sys::Reflect::get(value, &js_key)?)? {
+                    obj.insert(key, value);
+                }
+            }
+        }
+    }
+    Ok(object)
+}